@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use crate::{
+    drivers::{AbsoluteMotionMoved, Event, ManyMouseEvent},
+    frame::AbsoluteAxis,
+};
+
+/// Combines the separate X and Y events of absolute devices into one position.
+#[derive(Default)]
+pub(crate) struct AbsoluteTracker {
+    axes: HashMap<usize, (Option<AbsoluteAxis>, Option<AbsoluteAxis>)>,
+}
+
+impl AbsoluteTracker {
+    /// The last normalized position of the device, if both axes are known.
+    pub(crate) fn position(&self, device_id: usize) -> Option<(f32, f32)> {
+        match self.axes.get(&device_id) {
+            Some((Some(x), Some(y))) => Some((x.normalized(), y.normalized())),
+            _ => None,
+        }
+    }
+
+    /// Keeps up with the axes of absolute motion, see `position` for where that got to.
+    pub(crate) fn track(&mut self, event: &Event) {
+        match event.event {
+            ManyMouseEvent::AbsoluteMotion { moved, min, max } => {
+                let axes = self.axes.entry(event.device_id).or_default();
+                match moved {
+                    AbsoluteMotionMoved::X(value) => {
+                        axes.0 = Some(AbsoluteAxis { value, min, max })
+                    }
+                    AbsoluteMotionMoved::Y(value) => {
+                        axes.1 = Some(AbsoluteAxis { value, min, max })
+                    }
+                }
+            }
+            ManyMouseEvent::Disconnect => {
+                self.axes.remove(&event.device_id);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    drivers::{Event, ManyMouseEvent},
+    timestamps::EventTimes,
+};
+
+/* The fastest mice report at 8 kHz, reports closer together than this must share a time. */
+const MIN_INTERVAL: Duration = Duration::from_micros(125);
+/* After being idle for this long, the next motion starts from standstill. */
+const IDLE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccelProfile {
+    /// Every delta gets multiplied by `factor`, no matter how fast the mouse moves.
+    Flat { factor: f32 },
+    /// The factor grows by `gain` for every device unit per millisecond, capped at `max_factor`.
+    Linear {
+        factor: f32,
+        gain: f32,
+        max_factor: f32,
+    },
+    /// Similar to libinput's adaptive profile. Slow movements get slowed down for precision,
+    /// fast ones get sped up. `speed` goes from -1.0 (slowest) to 1.0 (fastest).
+    Adaptive { speed: f32 },
+}
+
+impl Default for AccelProfile {
+    fn default() -> Self {
+        AccelProfile::Flat { factor: 1.0 }
+    }
+}
+
+impl AccelProfile {
+    /// `velocity` is in device units per millisecond.
+    pub fn factor(&self, velocity: f32) -> f32 {
+        match *self {
+            AccelProfile::Flat { factor } => factor,
+            AccelProfile::Linear {
+                factor,
+                gain,
+                max_factor,
+            } => (factor + gain * velocity).min(max_factor),
+            AccelProfile::Adaptive { speed } => {
+                /* same magic numbers as libinput, assuming a 1000 dpi mouse. */
+                let speed = speed.clamp(-1.0, 1.0);
+                let threshold = (0.4 - 0.25 * speed).max(0.2);
+                let max_factor = 2.0 + 2.0 * speed;
+                let incline = 1.1 + speed;
+                let factor = if velocity < 0.07 {
+                    10.0 * velocity + 0.3
+                } else if velocity < threshold {
+                    1.0
+                } else {
+                    incline * (velocity - threshold) + 1.0
+                };
+                factor.min(max_factor)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AcceleratedMotion {
+    pub x: f32,
+    pub y: f32,
+    /// Whole units to move by. What doesn't fit is carried over to the next motion.
+    pub whole_x: i32,
+    pub whole_y: i32,
+}
+
+#[derive(Default)]
+struct DeviceState {
+    last_report: Option<Instant>,
+    remainder_x: f32,
+    remainder_y: f32,
+}
+
+impl DeviceState {
+    /// Returns the speed in device units per millisecond.
+    fn track(&mut self, x: i32, y: i32, time: Instant) -> f32 {
+        let interval = match self.last_report.replace(time) {
+            Some(last) => time
+                .saturating_duration_since(last)
+                .clamp(MIN_INTERVAL, IDLE),
+            None => IDLE,
+        };
+        (x as f32).hypot(y as f32) / (interval.as_secs_f32() * 1000.0)
+    }
+}
+
+/// Applies acceleration profiles to relative motion, per device.
+#[derive(Default)]
+pub struct Acceleration {
+    default_profile: AccelProfile,
+    profiles: HashMap<usize, AccelProfile>,
+    devices: HashMap<usize, DeviceState>,
+    times: EventTimes,
+}
+
+impl Acceleration {
+    pub fn new(default_profile: AccelProfile) -> Self {
+        Self {
+            default_profile,
+            ..Default::default()
+        }
+    }
+    pub fn set_default_profile(&mut self, profile: AccelProfile) {
+        self.default_profile = profile;
+    }
+    pub fn set_profile(&mut self, device_id: usize, profile: AccelProfile) {
+        self.profiles.insert(device_id, profile);
+    }
+    pub fn profile(&self, device_id: usize) -> AccelProfile {
+        self.profiles
+            .get(&device_id)
+            .copied()
+            .unwrap_or(self.default_profile)
+    }
+
+    /// Accelerates relative motion events, using their timestamp to work out the speed.
+    /// Without one, the current time is used instead.
+    pub fn handle_event(&mut self, event: &Event) -> Option<AcceleratedMotion> {
+        match event.event {
+            ManyMouseEvent::RelativeMotion { x, y } => {
+                let time = self.times.time(event);
+                Some(self.accelerate(event.device_id, x, y, time))
+            }
+            ManyMouseEvent::Disconnect => {
+                self.devices.remove(&event.device_id);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Accelerates a delta that happened at `time`, with X and Y of one report together.
+    ///
+    /// If events are only polled once per frame and have no timestamp, feed the summed motion
+    /// of the frame instead of every event so the speed isn't measured from how fast they
+    /// were polled.
+    pub fn accelerate(
+        &mut self,
+        device_id: usize,
+        x: i32,
+        y: i32,
+        time: Instant,
+    ) -> AcceleratedMotion {
+        let profile = self.profile(device_id);
+        let state = self.devices.entry(device_id).or_default();
+        let factor = profile.factor(state.track(x, y, time));
+
+        let x = x as f32 * factor;
+        let y = y as f32 * factor;
+        let carried_x = x + state.remainder_x;
+        let carried_y = y + state.remainder_y;
+        let whole_x = carried_x.trunc();
+        let whole_y = carried_y.trunc();
+        state.remainder_x = carried_x - whole_x;
+        state.remainder_y = carried_y - whole_y;
+        AcceleratedMotion {
+            x,
+            y,
+            whole_x: whole_x as i32,
+            whole_y: whole_y as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_and_linear_factor() {
+        assert_eq!(AccelProfile::Flat { factor: 2.0 }.factor(5.0), 2.0);
+        let linear = AccelProfile::Linear {
+            factor: 1.0,
+            gain: 0.5,
+            max_factor: 2.0,
+        };
+        assert_eq!(linear.factor(0.0), 1.0);
+        assert_eq!(linear.factor(1.0), 1.5);
+        assert_eq!(linear.factor(10.0), 2.0);
+    }
+
+    #[test]
+    fn adaptive_factor() {
+        let adaptive = AccelProfile::Adaptive { speed: 0.0 };
+        /* slow for precision, then flat, then faster up to the cap. */
+        assert!((adaptive.factor(0.0) - 0.3).abs() < 1e-6);
+        assert_eq!(adaptive.factor(0.2), 1.0);
+        assert!((adaptive.factor(1.0) - 1.66).abs() < 1e-6);
+        assert_eq!(adaptive.factor(100.0), 2.0);
+        assert!(adaptive.factor(0.5) < AccelProfile::Adaptive { speed: 1.0 }.factor(0.5));
+    }
+
+    #[test]
+    fn diagonal_stays_diagonal() {
+        let mut acceleration = Acceleration::new(AccelProfile::Adaptive { speed: 0.0 });
+        let start = Instant::now();
+        for i in 0..10 {
+            let motion = acceleration.accelerate(0, 5, 5, start + Duration::from_millis(8 * i));
+            assert_eq!(motion.x, motion.y);
+        }
+    }
+
+    #[test]
+    fn remainder_is_carried_over() {
+        let mut acceleration = Acceleration::new(AccelProfile::Flat { factor: 0.4 });
+        let start = Instant::now();
+        let mut moved = 0;
+        for i in 0..5 {
+            let motion = acceleration.accelerate(0, 1, -1, start + Duration::from_millis(i));
+            assert_eq!(motion.whole_x, -motion.whole_y);
+            moved += motion.whole_x;
+        }
+        /* 5 * 0.4 is 2, nothing gets lost on the way. */
+        assert_eq!(moved, 2);
+    }
+}
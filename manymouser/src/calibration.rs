@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::drivers::DeviceIdentity;
+
+/* below this a reference point set can't tell the axes apart, like points on a single line. */
+const SINGULAR: f64 = 1e-12;
+
+/// Maps the normalized position of an absolute device onto where it really points.
+///
+/// Stored as a 3x3 matrix in row order, so `x` becomes
+/// `(m[0] * x + m[1] * y + m[2]) / (m[6] * x + m[7] * y + m[8])` and `y` the same with `m[3..6]`.
+/// Affine calibrations keep the bottom row at `[0.0, 0.0, 1.0]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub matrix: [f32; 9],
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+/// Where a device said it was while pointing at a known spot, both from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferencePoint {
+    /// What `Context::absolute_position` returned.
+    pub reported: (f32, f32),
+    /// Where it should have been.
+    pub target: (f32, f32),
+}
+
+/* Solves `a * x = b` with gaussian elimination, consuming both. */
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for column in 0..N {
+        let pivot =
+            (column..N).max_by(|&l, &r| a[l][column].abs().total_cmp(&a[r][column].abs()))?;
+        if a[pivot][column].abs() < SINGULAR {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        for row in column + 1..N {
+            let factor = a[row][column] / a[column][column];
+            let (above, below) = a.split_at_mut(row);
+            for (to, from) in below[0][column..].iter_mut().zip(&above[column][column..]) {
+                *to -= factor * from;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let rest: f64 = (row + 1..N).map(|i| a[row][i] * x[i]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+/* Least squares fit of `rows * x = targets` through the normal equations. */
+fn fit<const N: usize>(rows: &[([f64; N], f64)]) -> Option<[f64; N]> {
+    let mut a = [[0.0; N]; N];
+    let mut b = [0.0; N];
+    for (row, target) in rows {
+        for i in 0..N {
+            for j in 0..N {
+                a[i][j] += row[i] * row[j];
+            }
+            b[i] += row[i] * target;
+        }
+    }
+    solve(a, b)
+}
+
+impl Calibration {
+    /// Fits a calibration to the reference points.
+    ///
+    /// Three or more points give an affine calibration, which handles offset, scale, rotation
+    /// and shear. With four or more and `perspective` set it can also undo keystone, like a
+    /// light gun aimed at a screen from an angle. Extra points are averaged out.
+    /// Returns `None` if there aren't enough points or they are all on one line.
+    pub fn from_points(points: &[ReferencePoint], perspective: bool) -> Option<Self> {
+        let m = if perspective {
+            if points.len() < 4 {
+                return None;
+            }
+            let mut rows = Vec::with_capacity(points.len() * 2);
+            for point in points {
+                let ((x, y), (u, v)) = (point.reported, point.target);
+                let (x, y, u, v) = (f64::from(x), f64::from(y), f64::from(u), f64::from(v));
+                rows.push(([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u));
+                rows.push(([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v));
+            }
+            let h = fit(&rows)?;
+            [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0]
+        } else {
+            if points.len() < 3 {
+                return None;
+            }
+            let row = |x: f32, y: f32| [f64::from(x), f64::from(y), 1.0];
+            let xs: Vec<_> = points
+                .iter()
+                .map(|v| (row(v.reported.0, v.reported.1), f64::from(v.target.0)))
+                .collect();
+            let ys: Vec<_> = points
+                .iter()
+                .map(|v| (row(v.reported.0, v.reported.1), f64::from(v.target.1)))
+                .collect();
+            let a = fit(&xs)?;
+            let b = fit(&ys)?;
+            [a[0], a[1], a[2], b[0], b[1], b[2], 0.0, 0.0, 1.0]
+        };
+        let mut matrix = [0.0; 9];
+        for (to, from) in matrix.iter_mut().zip(m.iter()) {
+            *to = *from as f32;
+        }
+        Some(Self { matrix })
+    }
+
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let m = &self.matrix;
+        let w = m[6] * x + m[7] * y + m[8];
+        /* only possible for points beyond the horizon of a perspective calibration. */
+        if w == 0.0 {
+            return (x, y);
+        }
+        (
+            (m[0] * x + m[1] * y + m[2]) / w,
+            (m[3] * x + m[4] * y + m[5]) / w,
+        )
+    }
+}
+
+/// Calibrations keyed by the device they belong to, so they survive reconnects and reboots.
+#[derive(Clone, Debug, Default)]
+pub struct CalibrationStore {
+    calibrations: HashMap<DeviceIdentity, Calibration>,
+}
+
+impl CalibrationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn insert(&mut self, identity: DeviceIdentity, calibration: Calibration) {
+        self.calibrations.insert(identity, calibration);
+    }
+    pub fn remove(&mut self, identity: &DeviceIdentity) -> Option<Calibration> {
+        self.calibrations.remove(identity)
+    }
+    pub fn get(&self, identity: &DeviceIdentity) -> Option<&Calibration> {
+        self.calibrations.get(identity)
+    }
+
+    /// One line per device: bus, vendor, product and version in hex, then the serial and
+    /// the name, then the nine numbers of the matrix. Fields are separated by tabs, so tabs,
+    /// line breaks and backslashes in the serial and name are written as `\t`, `\n`, `\r`
+    /// and `\\`.
+    pub fn save(&self) -> String {
+        let mut text = String::new();
+        for (identity, calibration) in &self.calibrations {
+            let matrix: Vec<String> = calibration.matrix.iter().map(|v| v.to_string()).collect();
+            text += &format!(
+                "{:04x} {:04x} {:04x} {:04x}\t{}\t{}\t{}\n",
+                identity.bus_type,
+                identity.vendor,
+                identity.product,
+                identity.version,
+                escape(&identity.serial),
+                escape(&identity.name),
+                matrix.join(" ")
+            );
+        }
+        text
+    }
+
+    /// Reads back what `save` wrote. Lines that can't be read are skipped, their numbers
+    /// are returned alongside, counting from 1.
+    pub fn load(text: &str) -> (Self, Vec<usize>) {
+        let mut store = Self::default();
+        let mut skipped = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Some((identity, calibration)) => store.insert(identity, calibration),
+                None => skipped.push(number + 1),
+            }
+        }
+        (store, skipped)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/* anything that isn't one of the escapes of `escape` is kept as it is. */
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn parse_line(line: &str) -> Option<(DeviceIdentity, Calibration)> {
+    let mut fields = line.split('\t');
+    let ids: Vec<u16> = fields
+        .next()?
+        .split(' ')
+        .map(|v| u16::from_str_radix(v, 16).ok())
+        .collect::<Option<_>>()?;
+    let serial = unescape(fields.next()?);
+    let name = unescape(fields.next()?);
+    let numbers: Vec<f32> = fields
+        .next()?
+        .split(' ')
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    if ids.len() != 4 || numbers.len() != 9 || fields.next().is_some() {
+        return None;
+    }
+    let mut matrix = [0.0; 9];
+    matrix.copy_from_slice(&numbers);
+    Some((
+        DeviceIdentity {
+            bus_type: ids[0],
+            vendor: ids[1],
+            product: ids[2],
+            version: ids[3],
+            serial,
+            name,
+        },
+        Calibration { matrix },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(serial: &str, name: &str) -> DeviceIdentity {
+        DeviceIdentity {
+            bus_type: 0x3,
+            vendor: 0x1209,
+            product: 0xbeef,
+            version: 0x110,
+            serial: serial.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let calibration = Calibration {
+            matrix: [1.5, 0.25, -0.125, 0.0, 2.0, 0.5, 0.001, -0.002, 1.0],
+        };
+        let mut store = CalibrationStore::new();
+        store.insert(identity("", "Plain Gun"), Calibration::default());
+        store.insert(identity("A\tB", "Odd\tName\r\nwith \\ in it"), calibration);
+        let (loaded, skipped) = CalibrationStore::load(&store.save());
+        assert!(skipped.is_empty());
+        assert_eq!(
+            loaded.get(&identity("", "Plain Gun")),
+            Some(&Calibration::default())
+        );
+        assert_eq!(
+            loaded.get(&identity("A\tB", "Odd\tName\r\nwith \\ in it")),
+            Some(&calibration)
+        );
+    }
+
+    #[test]
+    fn load_skips_broken_lines() {
+        let text = "0003 1209 beef 0110\t\tGun\t1 0 0 0 1 0 0 0 1\n\nnot a calibration\n";
+        let (loaded, skipped) = CalibrationStore::load(text);
+        assert_eq!(skipped, [3]);
+        assert_eq!(
+            loaded.get(&identity("", "Gun")),
+            Some(&Calibration::default())
+        );
+        assert_eq!(loaded.calibrations.len(), 1);
+    }
+
+    fn assert_maps(calibration: &Calibration, points: &[ReferencePoint]) {
+        for point in points {
+            let (x, y) = calibration.apply(point.reported.0, point.reported.1);
+            assert!(
+                (x - point.target.0).abs() < 1e-3,
+                "{:?} gave x {}",
+                point,
+                x
+            );
+            assert!(
+                (y - point.target.1).abs() < 1e-3,
+                "{:?} gave y {}",
+                point,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn affine_fit() {
+        /* scaled by half, moved by a quarter and flipped upside down. */
+        let points: Vec<_> = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| ReferencePoint {
+                reported: (x, y),
+                target: (x * 0.5 + 0.25, 1.0 - y),
+            })
+            .collect();
+        let calibration = Calibration::from_points(&points, false).unwrap();
+        assert_maps(&calibration, &points);
+        assert_eq!(&calibration.matrix[6..], &[0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn perspective_fit() {
+        /* the screen as seen from below and to the left, a keystoned quadrilateral. */
+        let points = [
+            ReferencePoint {
+                reported: (0.1, 0.2),
+                target: (0.0, 0.0),
+            },
+            ReferencePoint {
+                reported: (0.8, 0.1),
+                target: (1.0, 0.0),
+            },
+            ReferencePoint {
+                reported: (0.2, 0.9),
+                target: (0.0, 1.0),
+            },
+            ReferencePoint {
+                reported: (0.95, 0.75),
+                target: (1.0, 1.0),
+            },
+        ];
+        let calibration = Calibration::from_points(&points, true).unwrap();
+        assert_maps(&calibration, &points);
+    }
+
+    #[test]
+    fn too_few_or_collinear_points() {
+        let point = |x: f32, y: f32| ReferencePoint {
+            reported: (x, y),
+            target: (x, y),
+        };
+        assert_eq!(
+            Calibration::from_points(&[point(0.0, 0.0), point(1.0, 1.0)], false),
+            None
+        );
+        assert_eq!(
+            Calibration::from_points(&[point(0.0, 0.0), point(0.5, 0.5), point(1.0, 1.0)], false),
+            None
+        );
+        assert_eq!(
+            Calibration::from_points(&[point(0.0, 0.0), point(1.0, 0.0), point(0.0, 1.0)], true),
+            None
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::{
+    drivers::{AbsoluteMotionMoved, Event, ManyMouseEvent},
+    frame::AbsoluteAxis,
+    Context,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Bounds {
+    pub fn new(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Self {
+        Self {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+    fn center(&self) -> (f32, f32) {
+        (
+            (self.min_x + self.max_x) / 2.0,
+            (self.min_y + self.max_y) / 2.0,
+        )
+    }
+}
+
+/// What happens when a cursor moves past the edge of its bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeBehavior {
+    Clamp,
+    Wrap,
+}
+
+fn keep_in_range(value: f32, min: f32, max: f32, edge: EdgeBehavior) -> f32 {
+    if max <= min {
+        return min;
+    }
+    match edge {
+        EdgeBehavior::Clamp => value.clamp(min, max),
+        EdgeBehavior::Wrap => min + (value - min).rem_euclid(max - min),
+    }
+}
+
+/// Keeps an independent on-screen cursor for every device.
+///
+/// Relative devices move their cursor by their motion times their sensitivity,
+/// absolute devices get their range mapped onto the bounds.
+pub struct Cursors {
+    bounds: Bounds,
+    edge: EdgeBehavior,
+    default_sensitivity: f32,
+    sensitivity: HashMap<usize, f32>,
+    positions: HashMap<usize, (f32, f32)>,
+}
+
+impl Cursors {
+    pub fn new(bounds: Bounds) -> Self {
+        Self {
+            bounds,
+            edge: EdgeBehavior::Clamp,
+            default_sensitivity: 1.0,
+            sensitivity: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+    pub fn set_bounds(&mut self, bounds: Bounds) {
+        self.bounds = bounds;
+        for id in self.positions.keys().copied().collect::<Vec<_>>() {
+            self.move_to(id, self.positions[&id], EdgeBehavior::Clamp);
+        }
+    }
+    pub fn set_edge_behavior(&mut self, edge: EdgeBehavior) {
+        self.edge = edge;
+    }
+    pub fn set_default_sensitivity(&mut self, sensitivity: f32) {
+        self.default_sensitivity = sensitivity;
+    }
+    pub fn set_sensitivity(&mut self, device_id: usize, sensitivity: f32) {
+        self.sensitivity.insert(device_id, sensitivity);
+    }
+    pub fn sensitivity(&self, device_id: usize) -> f32 {
+        self.sensitivity
+            .get(&device_id)
+            .copied()
+            .unwrap_or(self.default_sensitivity)
+    }
+    pub fn position(&self, device_id: usize) -> Option<(f32, f32)> {
+        self.positions.get(&device_id).copied()
+    }
+    pub fn positions(&self) -> impl Iterator<Item = (usize, (f32, f32))> + '_ {
+        self.positions.iter().map(|(id, pos)| (*id, *pos))
+    }
+    pub fn set_position(&mut self, device_id: usize, position: (f32, f32)) {
+        self.move_to(device_id, position, EdgeBehavior::Clamp);
+    }
+
+    /// Polls `context` and updates the cursors with the event before handing it back.
+    pub fn poll(&mut self, context: &mut Context) -> Option<Event> {
+        let event = context.poll()?;
+        self.handle_event(&event);
+        Some(event)
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        let id = event.device_id;
+        match event.event {
+            ManyMouseEvent::RelativeMotion { x, y } => {
+                let sensitivity = self.sensitivity(id);
+                let (cur_x, cur_y) = self.current(id);
+                self.move_to(
+                    id,
+                    (
+                        cur_x + x as f32 * sensitivity,
+                        cur_y + y as f32 * sensitivity,
+                    ),
+                    self.edge,
+                );
+            }
+            ManyMouseEvent::AbsoluteMotion { moved, min, max } => {
+                let (cur_x, cur_y) = self.current(id);
+                let bounds = self.bounds;
+                let position = match moved {
+                    AbsoluteMotionMoved::X(value) => {
+                        let scale = AbsoluteAxis { value, min, max }.normalized();
+                        (bounds.min_x + scale * (bounds.max_x - bounds.min_x), cur_y)
+                    }
+                    AbsoluteMotionMoved::Y(value) => {
+                        let scale = AbsoluteAxis { value, min, max }.normalized();
+                        (cur_x, bounds.min_y + scale * (bounds.max_y - bounds.min_y))
+                    }
+                };
+                //absolute devices always land inside the bounds, so wrapping would only
+                //ever send the far edge back to the start.
+                self.move_to(id, position, EdgeBehavior::Clamp);
+            }
+            ManyMouseEvent::NormalizedAbsoluteMotion { x, y }
+            | ManyMouseEvent::GunAim {
+                x,
+                y,
+                on_screen: true,
+            } => {
+                let bounds = self.bounds;
+                self.move_to(
+                    id,
+                    (
+                        bounds.min_x + x * (bounds.max_x - bounds.min_x),
+                        bounds.min_y + y * (bounds.max_y - bounds.min_y),
+                    ),
+                    EdgeBehavior::Clamp,
+                );
+            }
+            ManyMouseEvent::Disconnect => {
+                self.positions.remove(&id);
+            }
+            _ => {}
+        }
+    }
+
+    fn current(&self, device_id: usize) -> (f32, f32) {
+        self.position(device_id)
+            .unwrap_or_else(|| self.bounds.center())
+    }
+    fn move_to(&mut self, device_id: usize, (x, y): (f32, f32), edge: EdgeBehavior) {
+        let bounds = self.bounds;
+        let x = keep_in_range(x, bounds.min_x, bounds.max_x, edge);
+        let y = keep_in_range(y, bounds.min_y, bounds.max_y, edge);
+        self.positions.insert(device_id, (x, y));
+    }
+}
@@ -1,14 +1,135 @@
-use std::{borrow::Cow, ffi::CStr};
+use std::{borrow::Cow, ffi::CStr, time::Duration};
+
+use crate::frame::AbsoluteAxis;
 
 mod bindgen;
 pub(crate) mod linux_evdev;
 pub(crate) mod manymouse_driver;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Button {
     Left,
     Middle,
     Right,
+    /// Any button past the first three, counting from 0 like `Left` is 0.
+    Other(u32),
+    /// The trigger of a light gun.
+    Trigger,
+    /// The button of a light gun that reloads, on guns that have one.
+    Reload,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceKind {
+    Mouse,
+    /// Touchpads, tablets and anything else that reports where it is instead of how far it moved.
+    AbsolutePointer,
+    /// Reports every finger on it with `TouchDown`, `TouchMove` and `TouchUp`,
+    /// on top of the `AbsoluteMotion` of the first one.
+    Touchpad,
+    /// Reports every finger on it like a `Touchpad`, but with the position on the screen.
+    /// The touches replace the button of `AbsolutePointer`. See `Context::set_touch_matrix`.
+    Touchscreen,
+    /// Rotary knobs like the Surface Dial.
+    Dial,
+    /// Arcade light guns. Their aim is reported with `GunAim` instead of `AbsoluteMotion`
+    /// and their buttons as `Trigger` and `Reload`.
+    ///
+    /// They look like any other joystick with a trigger, so they are recognized by names like
+    /// "gun" or "Sinden". Use `Context::set_device_kind` for the ones that get missed.
+    LightGun,
+    /// Only there with `Context::set_keyboards_enabled`, reported with `Key`.
+    Keyboard,
+    /// Foot pedals, buzzers and button boxes that have nothing but buttons.
+    /// Their buttons are numbered in order, so the first one is `Left`.
+    Buttons,
+    /// 3D mice like the SpaceMouse, reported with `Motion6Dof`.
+    SixDof,
+    /// Graphics tablets. Reports `PenProximity` and `PenMotion` on top of the `AbsoluteMotion`
+    /// of the pen.
+    Tablet,
+}
+
+impl Button {
+    /* ManyMouse numbers buttons as left, right, middle and then whatever comes next. */
+    pub(crate) fn from_item(item: u32) -> Self {
+        match item {
+            0 => Button::Left,
+            1 => Button::Right,
+            2 => Button::Middle,
+            x => Button::Other(x),
+        }
+    }
+}
+
+/// What a device was doing when it got opened.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceState {
+    /// The evdev codes of every button and key that was held down, like `BTN_LEFT`.
+    pub keys: Vec<u16>,
+    pub absolute_x: Option<AbsoluteAxis>,
+    pub absolute_y: Option<AbsoluteAxis>,
+}
+
+/// What a device reports about itself, stays the same when it gets plugged in again.
+///
+/// Two identical devices only differ in their serial, and only if they have one. Devices that
+/// come back get their old id by the port they are plugged into first, so identical ones
+/// don't swap. The port isn't part of the identity, it changes when a device moves.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceIdentity {
+    pub bus_type: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    /// Empty if the device doesn't report one.
+    pub serial: String,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PenTool {
+    Pen,
+    /// The back end of the pen.
+    Eraser,
+}
+
+/// Which clock event timestamps are taken from, see `Context::set_clock`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clock {
+    /// Wall clock time, which jumps whenever the system time gets changed.
+    Realtime,
+    /// The clock `std::time::Instant` uses, the default.
+    Monotonic,
+    /// Like `Monotonic`, but keeps counting during suspend.
+    Boottime,
+}
+
+/// Whether to hand out exactly what the kernel sent, see `Context::set_raw_mode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawMode {
+    Off,
+    /// Every `Raw` event comes right before whatever it got translated into.
+    Alongside,
+    /// Nothing but `Raw` events.
+    Only,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    Released,
+    Pressed,
+    /// Sent over and over while a key is held down.
+    Repeat,
+}
+
+/// Scroll values are in fractions of a wheel detent, this many make up a whole one.
+pub const SCROLL_DETENT: i32 = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollAxis {
+    Vertical,
+    Horizontal,
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -23,6 +144,7 @@ pub enum ManyMouseEvent {
         side: Button,
         is_pressed: bool,
     },
+    /// How far it moved since the last one, with the X and Y of one report together.
     RelativeMotion {
         x: i32,
         y: i32,
@@ -32,12 +154,91 @@ pub enum ManyMouseEvent {
         min: i32,
         max: i32,
     },
+    /// Both axes of an absolute device, from 0.0 to 1.0. See `Context::set_normalized_absolute`.
+    NormalizedAbsoluteMotion {
+        x: f32,
+        y: f32,
+    },
+    /// Where a light gun points, with both axes from 0.0 to 1.0 and calibrated if there is a
+    /// calibration. `on_screen` is false when it points outside of that range, which only
+    /// a calibrated gun can tell. There is one for every report of the gun.
+    GunAim {
+        x: f32,
+        y: f32,
+        on_screen: bool,
+    },
+    /// `value` is in 1/120ths of a detent (see `SCROLL_DETENT`), positive is up or right.
     Scroll {
+        axis: ScrollAxis,
+        value: i32,
+    },
+    /// A finger touched a multitouch surface. `contact` identifies it until its `TouchUp`.
+    /// The position goes from 0.0 to 1.0 on both axes.
+    TouchDown {
+        contact: i32,
+        x: f32,
+        y: f32,
+    },
+    TouchMove {
+        contact: i32,
+        x: f32,
+        y: f32,
+    },
+    TouchUp {
+        contact: i32,
+    },
+    /// A tool came close enough to a tablet to be tracked, or left again.
+    PenProximity {
+        tool: PenTool,
+        in_proximity: bool,
+    },
+    /// Pressure and distance go from 0.0 to 1.0, tilt from -1.0 to 1.0 with 0.0 being upright.
+    /// Axes the tablet doesn't have stay at 0.0.
+    PenMotion {
+        tool: PenTool,
+        pressure: f32,
+        distance: f32,
+        tilt_x: f32,
+        tilt_y: f32,
+    },
+    /// How far the cap of a 3D mouse is pushed along and turned around each axis, in the raw
+    /// units of the device. Sent once for every report of the device.
+    Motion6Dof {
+        tx: i32,
+        ty: i32,
+        tz: i32,
+        rx: i32,
+        ry: i32,
+        rz: i32,
+    },
+    /// A key of a keyboard. `code` is the evdev keycode, like `KEY_A` from
+    /// `linux/input-event-codes.h`.
+    Key {
+        code: u16,
+        state: KeyState,
+    },
+    /// A knob got turned, see `DeviceKind::Dial`.
+    Dial {
+        delta: i32,
+    },
+    /// Exactly what the kernel sent, including `EV_SYN`, `EV_MSC` and codes nothing else
+    /// knows about. Only with `Context::set_raw_mode`.
+    Raw {
+        event_type: u16,
+        code: u16,
         value: i32,
-        min: i32,
-        max: i32,
     },
     Disconnect,
+    /// Access to the device got revoked with `Context::revoke_device`. When another process
+    /// revokes it, that looks the same as unplugging it, so it reports `Disconnect` instead
+    /// and `Reconnect` once it can be opened again.
+    /// Whatever it held got released before this. Nothing more comes in until it is resumed.
+    Suspend,
+    /// The device is back after `Context::resume_device`.
+    Resume,
+    /// A device that disconnected came back and got its old id again.
+    /// Whatever it held was released before its `Disconnect`.
+    Reconnect,
     //TODO: Can't find what this does/what it needs :(
     Max {},
 }
@@ -46,12 +247,54 @@ pub enum ManyMouseEvent {
 pub struct Event {
     pub device_id: usize,
     pub event: ManyMouseEvent,
+    /// When the kernel saw it, on the clock picked with `Context::set_clock`.
+    /// `None` if the driver doesn't know, or for events nothing was read for, like `Disconnect`.
+    pub timestamp: Option<Duration>,
 }
 
 pub(crate) trait Driver {
     fn poll(&mut self) -> Option<Event>;
     fn driver_name(&self) -> Cow<'static, str>;
     fn device_name(&self, id: usize) -> Option<&CStr>;
+    fn device_kind(&self, id: usize) -> Option<DeviceKind>;
+    fn device_identity(&self, _id: usize) -> Option<DeviceIdentity> {
+        None
+    }
     fn get_all_mouse_names(&self) -> Box<dyn Iterator<Item = &CStr> + '_>;
     fn get_all_ids(&self) -> Box<dyn Iterator<Item = usize> + '_>;
+    /// Returns false if the driver can't do this conversion.
+    fn set_absolute_to_relative(&mut self, _enabled: bool) -> bool {
+        false
+    }
+    /// Returns false if the driver can't tell keyboards apart.
+    fn set_keyboards_enabled(&mut self, _enabled: bool) -> bool {
+        false
+    }
+    fn device_state(&self, _id: usize) -> Option<DeviceState> {
+        None
+    }
+    /// Returns false if the driver can't find out what a device is doing right now.
+    fn queue_state(&mut self, _id: usize) -> bool {
+        false
+    }
+    /// Returns false if the driver can't tell kinds apart or there is no such device.
+    fn set_device_kind(&mut self, _id: usize, _kind: DeviceKind) -> bool {
+        false
+    }
+    /// Returns false if the driver doesn't see what the kernel sent.
+    fn set_raw_mode(&mut self, _mode: RawMode) -> bool {
+        false
+    }
+    /// Returns false if the driver has no timestamps to pick a clock for.
+    fn set_clock(&mut self, _clock: Clock) -> bool {
+        false
+    }
+    /// Returns false if the driver can't revoke access to a device.
+    fn revoke_device(&mut self, _id: usize) -> bool {
+        false
+    }
+    /// Returns false if the driver can't reopen a device.
+    fn resume_device(&mut self, _id: usize) -> bool {
+        false
+    }
 }
@@ -1,7 +1,12 @@
+mod multitouch;
+mod six_dof;
+mod tablet;
+
 use std::{
     borrow::Cow,
+    collections::{HashMap, VecDeque},
     convert::{TryFrom, TryInto},
-    ffi::{CStr, OsStr},
+    ffi::{CStr, CString, OsStr},
     fs::File,
     io::Read,
     mem::{self, MaybeUninit},
@@ -9,24 +14,49 @@ use std::{
         raw::c_ushort,
         unix::prelude::{AsRawFd, FromRawFd, OsStrExt},
     },
+    time::{Duration, Instant},
 };
 
-use evdev::raw::{eviocgabs, eviocgbit, eviocgname, input_absinfo};
+use evdev::raw::{
+    eviocgabs, eviocgbit, eviocgid, eviocgkey, eviocgname, eviocgphys, eviocgprop, eviocguniq,
+    eviocrevoke, input_absinfo, input_id,
+};
 use input_linux_sys::{
-    input_event, ABS_MAX, ABS_X, ABS_Y, BTN_BACK, BTN_LEFT, BTN_MISC, BTN_MOUSE, BTN_STYLUS,
-    BTN_STYLUS2, BTN_TOUCH, EV_ABS, EV_KEY, EV_REL, KEY_MAX, REL_DIAL, REL_HWHEEL, REL_MAX,
-    REL_WHEEL, REL_X, REL_Y,
+    ev_set_clockid, input_event, ABS_MAX, ABS_MT_POSITION_X, ABS_MT_POSITION_Y, ABS_MT_SLOT, ABS_X,
+    ABS_Y, BTN_BACK, BTN_GEAR_UP, BTN_LEFT, BTN_MISC, BTN_MOUSE, BTN_RIGHT, BTN_STYLUS,
+    BTN_STYLUS2, BTN_THUMB, BTN_TOOL_FINGER, BTN_TOOL_PEN, BTN_TOUCH, BTN_TRIGGER,
+    BTN_TRIGGER_HAPPY, BTN_TRIGGER_HAPPY40, EV_ABS, EV_KEY, EV_REL, EV_SYN, INPUT_PROP_DIRECT,
+    INPUT_PROP_MAX, KEY_A, KEY_ESC, KEY_MAX, KEY_SPACE, KEY_Z, REL_DIAL, REL_HWHEEL,
+    REL_HWHEEL_HI_RES, REL_MAX, REL_WHEEL, REL_WHEEL_HI_RES, REL_X, REL_Y, SYN_DROPPED, SYN_REPORT,
 };
 use mem::size_of;
 use nix::{
-    libc::{c_int, closedir, open, DIR, O_NONBLOCK, O_RDONLY, S_IFCHR},
+    libc::{
+        c_int, clockid_t, closedir, ino_t, open, suseconds_t, time_t, timeval, CLOCK_BOOTTIME,
+        CLOCK_MONOTONIC, CLOCK_REALTIME, DIR, ENODEV, O_NONBLOCK, O_RDONLY, S_IFCHR,
+    },
     sys::stat::stat,
 };
 
-use super::Driver;
+use super::{
+    Button, Clock, DeviceIdentity, DeviceKind, DeviceState, Driver, KeyState, RawMode,
+    SCROLL_DETENT,
+};
+use crate::frame::AbsoluteAxis;
+use multitouch::MultiTouch;
+use six_dof::SixDof;
+use tablet::Tablet;
 const E_AGAIN: i32 = 11;
 
 const MAX_MICE: usize = 32;
+const EVENT_SIZE: usize = size_of::<input_event>();
+/* enough for a few reports of a fast mouse, so a poll rarely costs more than one read. */
+const READ_EVENTS: usize = 64;
+/* how often to look for devices that went away, a hub reset can take a while to come back. */
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+/* light guns look just like joysticks with a trigger, only their name gives them away.
+ * this is a guess, anything it gets wrong can be fixed with Context::set_device_kind. */
+const LIGHT_GUN_NAMES: [&str; 3] = ["gun", "sinden", "aimtrak"];
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ManyMouseEventType {
@@ -36,6 +66,7 @@ pub enum ManyMouseEventType {
     Scroll = 3,
     _Disconnect = 4,
     _Max = 5,
+    Dial = 6,
 }
 
 #[derive(Debug)]
@@ -48,57 +79,457 @@ pub struct ManyMouseEvent {
     pub maxval: Option<i32>,
 }
 
+impl From<ManyMouseEvent> for super::Event {
+    fn from(v: ManyMouseEvent) -> Self {
+        super::Event {
+            device_id: v.device,
+            timestamp: None,
+            event: match v.event_type {
+                ManyMouseEventType::Absmotion => super::ManyMouseEvent::AbsoluteMotion {
+                    moved: if v.item == 0 {
+                        super::AbsoluteMotionMoved::X(v.value)
+                    } else {
+                        super::AbsoluteMotionMoved::Y(v.value)
+                    },
+                    max: v.maxval.unwrap(),
+                    min: v.minval.unwrap(),
+                },
+                ManyMouseEventType::Relmotion => super::ManyMouseEvent::RelativeMotion {
+                    x: if v.item == 0 { v.value } else { 0 },
+                    y: if v.item == 0 { 0 } else { v.value },
+                },
+                ManyMouseEventType::Button => {
+                    println!("{:?}", v);
+                    super::ManyMouseEvent::Button {
+                        side: Button::from_item(u32::from(v.item)),
+                        is_pressed: v.value == 1,
+                    }
+                }
+                ManyMouseEventType::Scroll => super::ManyMouseEvent::Scroll {
+                    axis: if v.item == 0 {
+                        super::ScrollAxis::Vertical
+                    } else {
+                        super::ScrollAxis::Horizontal
+                    },
+                    value: v.value,
+                },
+                ManyMouseEventType::Dial => super::ManyMouseEvent::Dial { delta: v.value },
+                ManyMouseEventType::_Disconnect => super::ManyMouseEvent::Disconnect,
+                ManyMouseEventType::_Max => super::ManyMouseEvent::Max {},
+            },
+        }
+    }
+}
+
 pub struct MouseStruct {
     pub fd: File,
+    pub path: CString,
     pub min_x: i32,
     pub min_y: i32,
     pub max_x: i32,
     pub max_y: i32,
     pub name: [u8; 64],
+    pub kind: DeviceKind,
+    pub identity: DeviceIdentity,
+    /* where it is plugged in, tells identical devices apart when they come back. */
+    pub phys: String,
+    pub initial_state: DeviceState,
+    /* the codes of every button of a DeviceKind::Buttons, their index is the item. */
+    pub buttons: Vec<c_ushort>,
+    pub hi_res_wheel: bool,
+    pub hi_res_hwheel: bool,
+    pub absolute_to_relative: bool,
+    pub last_x: Option<i32>,
+    pub last_y: Option<i32>,
+    /* the position as of the current report, handed out as one event at its SYN_REPORT. */
+    pub abs_x: Option<i32>,
+    pub abs_y: Option<i32>,
+    pub abs_changed: bool,
+    /* relative motion of the current report, handed out as one event at its SYN_REPORT. */
+    pub rel_x: i32,
+    pub rel_y: i32,
+    pub touch: Option<MultiTouch>,
+    pub tablet: Option<Tablet>,
+    pub six_dof: Option<SixDof>,
+    /* events waiting to be handed out, like all the touches of one SYN_REPORT */
+    pub pending: VecDeque<super::ManyMouseEvent>,
+    pub raw_mode: RawMode,
+    /* read and handed out raw, but not translated yet. */
+    pub raw_read: Option<input_event>,
+    /* the keys as they were last handed out, to tell what changed while events got dropped. */
+    pub keys: [u8; (KEY_MAX as usize / 8) + 1],
+    /* held when it got opened, so only in DeviceState::keys, and nothing said about it since. */
+    pub unreported: Vec<u16>,
+    pub abscaps: [u8; (ABS_MAX as usize / 8) + 1],
+    /* set by SYN_DROPPED, everything up to the next SYN_REPORT is incomplete. */
+    pub dropped: bool,
+    /* made up events that bring the state back in line with the kernel. */
+    pub resync: VecDeque<input_event>,
+    /* unplugged or revoked, only the releases of whatever was still held are left to hand out. */
+    pub gone: bool,
+    /* no longer wanted, Disconnect follows once the releases of whatever is held are out. */
+    pub closing: bool,
+    /* revoked by revoke_device, so reading failing doesn't mean it got unplugged. */
+    pub revoked: bool,
+    /* access got revoked, nothing comes in until resume_device opens it again. */
+    pub suspended: bool,
+    /* when the kernel saw the event being handled, on the clock of the device.
+     * None for the made up ones from resync, nothing was read for those. */
+    pub last_time: Option<Duration>,
+    /* whole events read in one go, handed out from read_pos on. */
+    pub read_buf: Vec<u8>,
+    pub read_pos: usize,
 }
 
 pub struct DriverContainer {
     pub mice: [Option<MouseStruct>; MAX_MICE],
     pub available_mice: usize,
+    pub absolute_to_relative: bool,
+    pub keyboards: bool,
+    pub raw_mode: RawMode,
+    /* ids of devices that went away, they get them back if they show up again. */
+    pub lost: Vec<(usize, DeviceIdentity, String)>,
+    /* nodes that aren't mice, by inode so a node number that gets reused is looked at again. */
+    pub rejected: HashMap<CString, ino_t>,
+    pub last_scan: Option<Instant>,
+    pub clock: Clock,
 }
 
 fn test_bit(array: &[u8], bit: c_int) -> u8 {
     array[bit as usize / 8] & (1 << (bit % 8))
 }
 
+fn set_clock(fd: &File, clock: Clock) {
+    let id: clockid_t = match clock {
+        Clock::Realtime => CLOCK_REALTIME,
+        Clock::Monotonic => CLOCK_MONOTONIC,
+        Clock::Boottime => CLOCK_BOOTTIME,
+    };
+    if let Err(x) = unsafe { ev_set_clockid(fd.as_raw_fd(), &id) } {
+        dbg!(x);
+    }
+}
+
+/* input_event is a timeval followed by type, code and value, without any padding. */
+fn decode(bytes: &[u8]) -> input_event {
+    let (sec, rest) = bytes.split_at(size_of::<time_t>());
+    let (usec, rest) = rest.split_at(size_of::<suseconds_t>());
+    input_event {
+        time: timeval {
+            tv_sec: time_t::from_ne_bytes(sec.try_into().unwrap()),
+            tv_usec: suseconds_t::from_ne_bytes(usec.try_into().unwrap()),
+        },
+        type_: u16::from_ne_bytes(rest[0..2].try_into().unwrap()),
+        code: u16::from_ne_bytes(rest[2..4].try_into().unwrap()),
+        value: i32::from_ne_bytes(rest[4..8].try_into().unwrap()),
+    }
+}
+
+fn synthetic(type_: c_int, code: c_int, value: i32) -> input_event {
+    input_event {
+        time: timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        },
+        type_: type_.try_into().unwrap(),
+        code: code.try_into().unwrap(),
+        value,
+    }
+}
+
+fn absinfo(fd: &File, code: c_int) -> Option<input_absinfo> {
+    let mut absinfo = input_absinfo::default();
+    match unsafe { eviocgabs(fd.as_raw_fd(), code.try_into().unwrap(), &mut absinfo) } {
+        Ok(_) => Some(absinfo),
+        Err(x) => {
+            dbg!(x);
+            None
+        }
+    }
+}
+
 impl MouseStruct {
-    pub fn poll_mouse(&mut self, device: usize) -> std::io::Result<Option<ManyMouseEvent>> {
+    /* Queues the release of everything that is held, followed by a SYN_REPORT. */
+    fn release_all(&mut self) {
+        let keys = self.keys;
+        for code in (0..=KEY_MAX).filter(|v| test_bit(&keys, *v) != 0) {
+            self.resync.push_back(synthetic(EV_KEY, code, 0));
+        }
+        for code in mem::take(&mut self.unreported) {
+            self.resync
+                .push_back(synthetic(EV_KEY, c_int::from(code), 0));
+        }
+        if let Some(touch) = &self.touch {
+            touch.lift_all(&mut self.resync);
+        }
+        self.resync.push_back(synthetic(EV_SYN, SYN_REPORT, 0));
+    }
+
+    /* Lets the device go as if it got unplugged, without waiting for the kernel. */
+    fn close(&mut self) {
+        self.closing = true;
+        self.dropped = false;
+        self.resync.clear();
+        /* nothing but raw events means nothing that needs releasing. */
+        if self.raw_mode != RawMode::Only {
+            self.release_all();
+        }
+    }
+
+    /* Queues whatever changed while events got dropped, followed by a SYN_REPORT. */
+    fn resync(&mut self) {
+        let mut keys = [0; (KEY_MAX as usize / 8) + 1];
+        if let Err(x) = unsafe { eviocgkey(self.fd.as_raw_fd(), &mut keys) } {
+            dbg!(x);
+            return;
+        }
+        for code in 0..=KEY_MAX {
+            let pressed = test_bit(&keys, code) != 0;
+            if pressed != (test_bit(&self.keys, code) != 0) {
+                self.resync
+                    .push_back(synthetic(EV_KEY, code, pressed as i32));
+            }
+        }
+        /* the multitouch axes only make sense per slot. */
+        let abscaps = self.abscaps;
+        for code in (0..ABS_MT_SLOT).filter(|v| test_bit(&abscaps, *v) != 0) {
+            if let Some(info) = absinfo(&self.fd, code) {
+                self.resync.push_back(synthetic(EV_ABS, code, info.value));
+            }
+        }
+        if let Some(touch) = &self.touch {
+            touch.resync(&self.fd, &mut self.resync);
+        }
+        self.resync.push_back(synthetic(EV_SYN, SYN_REPORT, 0));
+    }
+
+    /* reads as many events as fit, read_buf ends up empty if there weren't any. */
+    fn refill(&mut self) -> std::io::Result<()> {
+        self.read_buf.resize(READ_EVENTS * EVENT_SIZE, 0);
+        self.read_pos = 0;
+        let read = match self.fd.read(&mut self.read_buf) {
+            Ok(x) => x,
+            Err(x) => {
+                self.read_buf.clear();
+                return Err(x);
+            }
+        };
+        /* the kernel never splits an event, so this only drops a short read. */
+        self.read_buf.truncate(read - read % EVENT_SIZE);
+        Ok(())
+    }
+
+    /* the same for motion, so X and Y get accelerated as the one move they are. */
+    fn report_motion(&mut self) {
+        let (x, y) = (mem::take(&mut self.rel_x), mem::take(&mut self.rel_y));
+        if x != 0 || y != 0 {
+            self.pending
+                .push_back(super::ManyMouseEvent::RelativeMotion { x, y });
+        }
+    }
+
+    /* X and Y of one report together, so a diagonal move doesn't turn into two steps. */
+    fn report_position(&mut self) {
+        if !mem::take(&mut self.abs_changed) || self.absolute_to_relative {
+            return;
+        }
+        if let (Some(x), Some(y)) = (self.abs_x, self.abs_y) {
+            let x = AbsoluteAxis {
+                value: x,
+                min: self.min_x,
+                max: self.max_x,
+            };
+            let y = AbsoluteAxis {
+                value: y,
+                min: self.min_y,
+                max: self.max_y,
+            };
+            let (x, y) = (x.normalized(), y.normalized());
+            self.pending
+                .push_back(if self.kind == DeviceKind::LightGun {
+                    /* without a calibration the whole range of the gun is the screen. */
+                    super::ManyMouseEvent::GunAim {
+                        x,
+                        y,
+                        on_screen: true,
+                    }
+                } else {
+                    super::ManyMouseEvent::NormalizedAbsoluteMotion { x, y }
+                });
+        }
+    }
+
+    pub fn poll_mouse(&mut self, device: usize) -> std::io::Result<Option<super::Event>> {
         loop {
-            //struct input_event event;
-            let mut buf: [u8; size_of::<input_event>()] = [
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            ];
-            let res = self.fd.read_exact(&mut buf);
-            if let Err(x) = res {
-                if matches!(x.raw_os_error(), Some(E_AGAIN)) {
-                    return Ok(None);
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(super::Event {
+                    device_id: device,
+                    event,
+                    timestamp: self.last_time,
+                }));
+            }
+            let raw_read = self.raw_read.take();
+            let resynced = raw_read.is_none() && !self.resync.is_empty();
+            if resynced {
+                self.last_time = None;
+            }
+            let event = match raw_read.or_else(|| self.resync.pop_front()) {
+                Some(x) => x,
+                None => {
+                    if self.suspended {
+                        return Ok(None);
+                    }
+                    if self.closing {
+                        return Ok(Some(super::Event {
+                            device_id: device,
+                            event: super::ManyMouseEvent::Disconnect,
+                            timestamp: None,
+                        }));
+                    }
+                    if self.read_pos == self.read_buf.len() {
+                        if let Err(x) = self.refill() {
+                            if matches!(x.raw_os_error(), Some(E_AGAIN)) {
+                                return Ok(None);
+                            }
+                            if matches!(x.raw_os_error(), Some(ENODEV)) {
+                                if !self.gone {
+                                    self.gone = true;
+                                    self.dropped = false;
+                                    self.resync.clear();
+                                    /* nothing but raw events means nothing that needs releasing. */
+                                    if self.raw_mode != RawMode::Only {
+                                        self.release_all();
+                                    }
+                                    continue;
+                                }
+                                /* a revoked fd fails like an unplugged one. the node of an
+                                 * unplugged one sticks around for a moment, so only trust
+                                 * what we did ourselves. */
+                                if self.revoked {
+                                    self.suspended = true;
+                                    return Ok(Some(super::Event {
+                                        device_id: device,
+                                        event: super::ManyMouseEvent::Suspend,
+                                        timestamp: None,
+                                    }));
+                                }
+                                return Ok(Some(super::Event {
+                                    device_id: device,
+                                    event: super::ManyMouseEvent::Disconnect,
+                                    timestamp: None,
+                                }));
+                            }
+                            return Err(x);
+                        }
+                        if self.read_buf.is_empty() {
+                            return Ok(None);
+                        }
+                    }
+                    let event = decode(&self.read_buf[self.read_pos..self.read_pos + EVENT_SIZE]);
+                    self.read_pos += EVENT_SIZE;
+                    self.last_time = Some(Duration::new(
+                        event.time.tv_sec as u64,
+                        event.time.tv_usec as u32 * 1000,
+                    ));
+                    if self.raw_mode != RawMode::Off {
+                        /* alongside: the translation of it comes with the next call. */
+                        if self.raw_mode == RawMode::Alongside {
+                            self.raw_read = Some(event);
+                        }
+                        return Ok(Some(super::Event {
+                            device_id: device,
+                            event: super::ManyMouseEvent::Raw {
+                                event_type: event.type_,
+                                code: event.code,
+                                value: event.value,
+                            },
+                            timestamp: self.last_time,
+                        }));
+                    }
+                    event
+                }
+            };
+            if self.dropped {
+                /* the kernel drops up to and including the next SYN_REPORT. */
+                if event.type_ == EV_SYN.try_into().unwrap()
+                    && event.code == SYN_REPORT.try_into().unwrap()
+                {
+                    self.dropped = false;
+                    self.resync();
+                }
+                continue;
+            }
+            if event.type_ == EV_KEY.try_into().unwrap() {
+                let code = usize::from(event.code);
+                self.unreported.retain(|v| *v != event.code);
+                if code <= KEY_MAX as usize {
+                    if event.value == 0 {
+                        self.keys[code / 8] &= !(1 << (code % 8));
+                    } else {
+                        self.keys[code / 8] |= 1 << (code % 8);
+                    }
+                }
+            }
+            if let Some(six_dof) = &mut self.six_dof {
+                if six_dof.handle(&event) {
+                    continue;
                 }
-                return Err(x);
             }
-            let event: input_event = unsafe { std::mem::transmute(buf) };
-            //let event = buf[0].try_into().unwrap();
 
             let mut event_type;
             let item;
+            let mut value = event.value;
             let mut min_val = None;
             let mut max_val = None;
-            if event.type_ == EV_REL.try_into().unwrap() {
+            if event.type_ == EV_SYN.try_into().unwrap() {
+                if event.code == SYN_DROPPED.try_into().unwrap() {
+                    /* our buffer overflowed, whatever came in before this is incomplete. */
+                    self.dropped = true;
+                    self.resync.clear();
+                    self.rel_x = 0;
+                    self.rel_y = 0;
+                } else if event.code == SYN_REPORT.try_into().unwrap() {
+                    if let Some(touch) = &mut self.touch {
+                        touch.report(&mut self.pending);
+                    }
+                    if let Some(tablet) = &mut self.tablet {
+                        tablet.report(&mut self.pending);
+                    }
+                    if let Some(six_dof) = &mut self.six_dof {
+                        six_dof.report(&mut self.pending);
+                    }
+                    self.report_motion();
+                    self.report_position();
+                }
+                continue;
+            } else if event.type_ == EV_REL.try_into().unwrap() {
                 event_type = ManyMouseEventType::Relmotion;
-                if (event.code == REL_X.try_into().unwrap())
-                    || (event.code == REL_DIAL.try_into().unwrap())
-                {
+                if event.code == REL_X.try_into().unwrap() {
+                    item = 0;
+                } else if event.code == REL_DIAL.try_into().unwrap() {
+                    event_type = ManyMouseEventType::Dial;
                     item = 0;
                 } else if event.code == REL_Y.try_into().unwrap() {
                     item = 1;
                 } else if event.code == REL_WHEEL.try_into().unwrap() {
+                    /* the kernel sends both, only use the high resolution one if there is one. */
+                    if self.hi_res_wheel {
+                        continue;
+                    }
                     event_type = ManyMouseEventType::Scroll;
                     item = 0;
+                    value = event.value * SCROLL_DETENT;
                 } else if event.code == REL_HWHEEL.try_into().unwrap() {
+                    if self.hi_res_hwheel {
+                        continue;
+                    }
+                    event_type = ManyMouseEventType::Scroll;
+                    item = 1;
+                    value = event.value * SCROLL_DETENT;
+                } else if event.code == REL_WHEEL_HI_RES.try_into().unwrap() {
+                    event_type = ManyMouseEventType::Scroll;
+                    item = 0;
+                } else if event.code == REL_HWHEEL_HI_RES.try_into().unwrap() {
                     event_type = ManyMouseEventType::Scroll;
                     item = 1;
                 } else {
@@ -107,6 +538,16 @@ impl MouseStruct {
             }
             /* if */
             else if event.type_ == EV_ABS.try_into().unwrap() {
+                if let Some(touch) = &mut self.touch {
+                    if touch.handle(&event) {
+                        continue;
+                    }
+                }
+                if let Some(tablet) = &mut self.tablet {
+                    if tablet.handle_abs(&event) {
+                        continue;
+                    }
+                }
                 event_type = ManyMouseEventType::Absmotion;
                 if event.code == ABS_X.try_into().unwrap() {
                     item = 0;
@@ -121,9 +562,77 @@ impl MouseStruct {
                 } else {
                     continue;
                 } /* else */
+                if item == 0 {
+                    self.abs_x = Some(event.value);
+                } else {
+                    self.abs_y = Some(event.value);
+                }
+                self.abs_changed = true;
+                if self.absolute_to_relative {
+                    let last = if item == 0 {
+                        &mut self.last_x
+                    } else {
+                        &mut self.last_y
+                    };
+                    /* the first position after a touch only sets where we start from. */
+                    match last.replace(event.value) {
+                        Some(previous) if previous != event.value => {
+                            event_type = ManyMouseEventType::Relmotion;
+                            value = event.value - previous;
+                            min_val = None;
+                            max_val = None;
+                        }
+                        _ => continue,
+                    }
+                }
             } else if event.type_ == EV_KEY.try_into().unwrap() {
+                if self.kind == DeviceKind::Keyboard {
+                    let state = match event.value {
+                        0 => KeyState::Released,
+                        1 => KeyState::Pressed,
+                        _ => KeyState::Repeat,
+                    };
+                    return Ok(Some(super::Event {
+                        device_id: device,
+                        event: super::ManyMouseEvent::Key {
+                            code: event.code,
+                            state,
+                        },
+                        timestamp: self.last_time,
+                    }));
+                }
+                if let Some(tablet) = &mut self.tablet {
+                    if tablet.handle_key(&event) {
+                        continue;
+                    }
+                }
+                if self.kind == DeviceKind::LightGun {
+                    let code = i32::from(event.code);
+                    let side = if code == BTN_LEFT || code == BTN_TRIGGER {
+                        Some(Button::Trigger)
+                    } else if code == BTN_RIGHT || code == BTN_THUMB {
+                        Some(Button::Reload)
+                    } else {
+                        None
+                    };
+                    if let Some(side) = side {
+                        return Ok(Some(super::Event {
+                            device_id: device,
+                            event: super::ManyMouseEvent::Button {
+                                side,
+                                is_pressed: event.value != 0,
+                            },
+                            timestamp: self.last_time,
+                        }));
+                    }
+                }
                 event_type = ManyMouseEventType::Button;
-                if (event.code >= BTN_LEFT.try_into().unwrap())
+                if self.kind == DeviceKind::Buttons {
+                    match self.buttons.iter().position(|v| *v == event.code) {
+                        Some(x) => item = x.try_into().unwrap(),
+                        None => continue,
+                    }
+                } else if (event.code >= BTN_LEFT.try_into().unwrap())
                     && (event.code <= BTN_BACK.try_into().unwrap())
                 {
                     item = event.code - c_ushort::try_from(BTN_MOUSE).unwrap();
@@ -134,6 +643,21 @@ impl MouseStruct {
                 {
                     item = event.code - c_ushort::try_from(BTN_MISC).unwrap();
                 } else if event.code == BTN_TOUCH.try_into().unwrap() {
+                    if let Some(touch) = &mut self.touch {
+                        touch.handle_touch(event.value != 0);
+                    }
+                    /* the touches themselves already say when a finger is on the screen. */
+                    if self.kind == DeviceKind::Touchscreen && !self.absolute_to_relative {
+                        continue;
+                    }
+                    if self.absolute_to_relative {
+                        /* finger lifted, so the next position is not a continuation of this one. */
+                        if event.value == 0 {
+                            self.last_x = None;
+                            self.last_y = None;
+                        }
+                        continue;
+                    }
                     /* tablet... */
                     item = 0;
                 } else if event.code == BTN_STYLUS.try_into().unwrap() {
@@ -149,26 +673,43 @@ impl MouseStruct {
             } else {
                 continue;
             }
-            return Ok(Some(ManyMouseEvent {
+            if event_type == ManyMouseEventType::Relmotion {
+                if item == 0 {
+                    self.rel_x += value;
+                } else {
+                    self.rel_y += value;
+                }
+                continue;
+            }
+            let mut translated: super::Event = ManyMouseEvent {
                 device,
                 event_type,
                 item,
-                value: event.value,
+                value,
                 minval: min_val,
                 maxval: max_val,
-            }));
+            }
+            .into();
+            translated.timestamp = self.last_time;
+            return Ok(Some(translated));
         }
     }
 }
 
 impl DriverContainer {
-    pub fn init_mouse(&mut self, _fname: &CStr, fd: File) -> bool {
+    pub fn init_mouse(&mut self, fname: &CStr, fd: File) -> bool {
+        set_clock(&fd, self.clock);
         //let mouse = self.mice[self.available_mice].as_mut().unwrap();
         let mut has_absolutes = 0;
-        let mut is_mouse = 0;
+        let mut has_multitouch = false;
+        let mut has_trigger = false;
+        let mut kind = None;
+        let mut hi_res_wheel = false;
+        let mut hi_res_hwheel = false;
         let mut relcaps: [u8; (REL_MAX as usize / 8) + 1] = [0, 0];
         let mut abscaps: [u8; (ABS_MAX as usize / 8) + 1] = [0, 0, 0, 0, 0, 0, 0, 0];
         let mut keycaps: [u8; (KEY_MAX as usize / 8) + 1] = [0; 96];
+        let mut propcaps: [u8; (INPUT_PROP_MAX as usize / 8) + 1] = [0; 4];
 
         if unsafe {
             eviocgbit(
@@ -183,6 +724,11 @@ impl DriverContainer {
             return false;
         };
 
+        /* older kernels don't know about properties, treat that as having none. */
+        if let Err(x) = unsafe { eviocgprop(fd.as_raw_fd(), &mut propcaps) } {
+            let _ = dbg!(x);
+        }
+
         match unsafe {
             eviocgbit(
                 fd.as_raw_fd(),
@@ -196,8 +742,16 @@ impl DriverContainer {
                     && (test_bit(&relcaps, REL_Y) != 0)
                     && test_bit(&keycaps, BTN_MOUSE) != 0
                 {
-                    is_mouse = 1;
+                    kind = Some(DeviceKind::Mouse);
+                } else if (test_bit(&relcaps, REL_DIAL) != 0)
+                    && (test_bit(&relcaps, REL_X) == 0)
+                    && (test_bit(&relcaps, REL_Y) == 0)
+                {
+                    /* a knob, like the surface dial. Often has nothing more than a button. */
+                    kind = Some(DeviceKind::Dial);
                 }
+                hi_res_wheel = test_bit(&relcaps, REL_WHEEL_HI_RES) != 0;
+                hi_res_hwheel = test_bit(&relcaps, REL_HWHEEL_HI_RES) != 0;
             }
             Err(x) => {
                 let _ = dbg!(x);
@@ -214,21 +768,97 @@ impl DriverContainer {
             Ok(_) => {
                 if (test_bit(&abscaps, ABS_X) != 0) && (test_bit(&abscaps, ABS_Y) != 0) {
                     /* might be a touch pad... */
-                    if test_bit(&keycaps, BTN_TOUCH) != 0 {
-                        is_mouse = 1; /* touch pad, touchscreen, or tablet. */
+                    if test_bit(&keycaps, BTN_TOOL_PEN) != 0 {
+                        /* graphics tablet. Pens hover, so it might not even have BTN_TOUCH. */
+                        kind = Some(DeviceKind::Tablet);
+                        has_absolutes = 1;
+                    } else if test_bit(&keycaps, BTN_TOUCH) != 0 {
+                        /* touch pad, touchscreen, or tablet. */
+                        kind = Some(DeviceKind::AbsolutePointer);
                         has_absolutes = 1;
+                        if test_bit(&propcaps, INPUT_PROP_DIRECT) != 0 {
+                            kind = Some(DeviceKind::Touchscreen);
+                        } else if test_bit(&keycaps, BTN_TOOL_FINGER) != 0 {
+                            kind = Some(DeviceKind::Touchpad);
+                        }
+                    } else {
+                        /* aimed without touching anything, like a light gun. */
+                        has_trigger = (test_bit(&keycaps, BTN_LEFT) != 0)
+                            || (test_bit(&keycaps, BTN_TRIGGER) != 0);
                     }
                 }
+                has_multitouch = (test_bit(&abscaps, ABS_MT_SLOT) != 0)
+                    && (test_bit(&abscaps, ABS_MT_POSITION_X) != 0)
+                    && (test_bit(&abscaps, ABS_MT_POSITION_Y) != 0);
             }
             Err(x) => {
                 let _ = dbg!(x);
             }
         }
 
-        if is_mouse == 0 {
-            return false;
+        let mut mouse_name: [u8; 64] = [0; 64];
+        if let Err(x) = unsafe { eviocgname(fd.as_raw_fd(), &mut mouse_name) } {
+            dbg!(x);
+            mouse_name = [
+                b'u', b'n', b'k', b'n', b'o', b'w', b'n', b'd', b'e', b'v', b'i', b'c', b'e', 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ];
+        }
+
+        /* a 3D mouse, the translation axes would otherwise make it look like anything else. */
+        let six_dof = SixDof::new(&relcaps, &abscaps, &keycaps, &propcaps);
+        if six_dof.is_some() {
+            kind = Some(DeviceKind::SixDof);
+            has_absolutes = 0;
+            has_trigger = false;
+        }
+
+        if has_trigger {
+            let name = String::from_utf8_lossy(&mouse_name).to_lowercase();
+            if LIGHT_GUN_NAMES.iter().any(|v| name.contains(v)) {
+                kind = Some(DeviceKind::LightGun);
+                has_absolutes = 1;
+            } else if kind.is_none() && test_bit(&keycaps, BTN_LEFT) != 0 {
+                /* a mouse that reports positions, like the tablet of a virtual machine. */
+                kind = Some(DeviceKind::AbsolutePointer);
+                has_absolutes = 1;
+            }
+        }
+
+        /* only when asked for, most programs still want keyboards to go to the focused window. */
+        if self.keyboards
+            && kind.is_none()
+            && [KEY_ESC, KEY_A, KEY_Z, KEY_SPACE]
+                .iter()
+                .all(|v| test_bit(&keycaps, *v) != 0)
+        {
+            kind = Some(DeviceKind::Keyboard);
         }
 
+        /* nothing to point with, so the buttons are all there is. */
+        let mut buttons = Vec::new();
+        if kind.is_none()
+            && test_bit(&relcaps, REL_X) == 0
+            && test_bit(&relcaps, REL_Y) == 0
+            && test_bit(&abscaps, ABS_X) == 0
+            && test_bit(&abscaps, ABS_Y) == 0
+        {
+            buttons = (BTN_MISC..=BTN_GEAR_UP)
+                .chain(BTN_TRIGGER_HAPPY..=BTN_TRIGGER_HAPPY40)
+                .filter(|v| test_bit(&keycaps, *v) != 0)
+                .map(|v| v.try_into().unwrap())
+                .collect();
+            if !buttons.is_empty() {
+                kind = Some(DeviceKind::Buttons);
+            }
+        }
+
+        let kind = match kind {
+            Some(x) => x,
+            None => return false,
+        };
+
         let mut mouse_min_x = 0;
         let mut mouse_min_y = 0;
         let mut mouse_max_x = 0;
@@ -257,25 +887,135 @@ impl DriverContainer {
             mouse_min_y = absinfo.minimum;
             mouse_max_y = absinfo.maximum;
         }
-        let mut mouse_name: [u8; 64] = [0; 64];
-        if let Err(x) = unsafe { eviocgname(fd.as_raw_fd(), &mut mouse_name) } {
+        let touch = if has_multitouch && has_absolutes != 0 {
+            MultiTouch::new(&fd)
+        } else if kind == DeviceKind::Touchscreen {
+            MultiTouch::single_touch(&fd)
+        } else {
+            None
+        };
+        let tablet = if kind == DeviceKind::Tablet {
+            Some(Tablet::new(&fd, &abscaps))
+        } else {
+            None
+        };
+
+        let mut initial_keys = [0; (KEY_MAX as usize / 8) + 1];
+        if let Err(x) = unsafe { eviocgkey(fd.as_raw_fd(), &mut initial_keys) } {
             dbg!(x);
-            mouse_name = [
-                b'u', b'n', b'k', b'n', b'o', b'w', b'n', b'd', b'e', b'v', b'i', b'c', b'e', 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            ];
         }
+        let initial_axis = |code| {
+            if test_bit(&abscaps, code) == 0 {
+                return None;
+            }
+            absinfo(&fd, code).map(|v| AbsoluteAxis {
+                value: v.value,
+                min: v.minimum,
+                max: v.maximum,
+            })
+        };
+        let initial_state = DeviceState {
+            keys: (0..=KEY_MAX)
+                .filter(|v| test_bit(&initial_keys, *v) != 0)
+                .map(|v| v.try_into().unwrap())
+                .collect(),
+            absolute_x: initial_axis(ABS_X),
+            absolute_y: initial_axis(ABS_Y),
+        };
 
-        let mouse = MouseStruct {
+        let mut id = input_id {
+            bustype: 0,
+            vendor: 0,
+            product: 0,
+            version: 0,
+        };
+        if let Err(x) = unsafe { eviocgid(fd.as_raw_fd(), &mut id) } {
+            dbg!(x);
+        }
+        /* most devices don't have a serial, the kernel hands out an empty string then. */
+        let mut serial: [u8; 64] = [0; 64];
+        if let Err(x) = unsafe { eviocguniq(fd.as_raw_fd(), &mut serial) } {
+            dbg!(x);
+        }
+        let mut phys: [u8; 64] = [0; 64];
+        if let Err(x) = unsafe { eviocgphys(fd.as_raw_fd(), &mut phys) } {
+            dbg!(x);
+        }
+        let text = |bytes: &[u8]| {
+            let end = bytes.iter().position(|v| *v == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let identity = DeviceIdentity {
+            bus_type: id.bustype,
+            vendor: id.vendor,
+            product: id.product,
+            version: id.version,
+            serial: text(&serial),
+            name: text(&mouse_name),
+        };
+
+        let mut mouse = MouseStruct {
             fd,
+            path: fname.to_owned(),
             min_x: mouse_min_x,
             min_y: mouse_min_y,
             max_x: mouse_max_x,
             max_y: mouse_max_y,
             name: mouse_name,
+            kind,
+            identity,
+            phys: text(&phys),
+            unreported: initial_state.keys.clone(),
+            initial_state,
+            buttons,
+            hi_res_wheel,
+            hi_res_hwheel,
+            absolute_to_relative: self.absolute_to_relative,
+            last_x: None,
+            last_y: None,
+            abs_x: None,
+            abs_y: None,
+            abs_changed: false,
+            rel_x: 0,
+            rel_y: 0,
+            touch,
+            tablet,
+            six_dof,
+            pending: VecDeque::new(),
+            raw_mode: self.raw_mode,
+            raw_read: None,
+            keys: [0; (KEY_MAX as usize / 8) + 1],
+            abscaps,
+            dropped: false,
+            resync: VecDeque::new(),
+            gone: false,
+            closing: false,
+            revoked: false,
+            suspended: false,
+            last_time: None,
+            read_buf: Vec::with_capacity(READ_EVENTS * EVENT_SIZE),
+            read_pos: 0,
         };
-        self.mice[self.available_mice] = Some(mouse);
+        /* the same port first, identical devices only differ in that. */
+        let lost = self
+            .lost
+            .iter()
+            .position(|v| v.1 == mouse.identity && v.2 == mouse.phys)
+            .or_else(|| self.lost.iter().position(|v| v.1 == mouse.identity));
+        match lost {
+            Some(i) => {
+                let (id, _, _) = self.lost.remove(i);
+                mouse.pending.push_back(super::ManyMouseEvent::Reconnect);
+                self.mice[id] = Some(mouse);
+            }
+            None => {
+                if self.available_mice >= MAX_MICE {
+                    return false;
+                }
+                self.mice[self.available_mice] = Some(mouse);
+                self.available_mice += 1;
+            }
+        }
 
         true
     }
@@ -301,17 +1041,22 @@ impl DriverContainer {
         devmajor = (statbuf.st_rdev & 0xFF00) >> 8;
         devminor = statbuf.st_rdev & 0x00FF;
         if (devmajor != 13) || (devminor < 64) || (devminor > 96) {
+            self.rejected.insert(fname.to_owned(), statbuf.st_ino);
             return false; /* not an evdev. */
         }
 
         let fd = unsafe { open(fname.as_ptr() as *const i8, O_RDONLY | O_NONBLOCK) };
         if fd == -1 {
-            return true;
+            return false;
         }
         let fd = unsafe { File::from_raw_fd(fd) };
         if self.init_mouse(fname, fd) {
             return true;
         }
+        /* unless it only didn't fit, it won't turn into a mouse later. */
+        if self.available_mice < MAX_MICE {
+            self.rejected.insert(fname.to_owned(), statbuf.st_ino);
+        }
         false
     }
     fn linux_evdev_init(&mut self) -> i32 {
@@ -328,7 +1073,21 @@ impl DriverContainer {
             return -1;
         }
 
-        let files = std::fs::read_dir("/dev/input").unwrap();
+        self.scan_devices();
+
+        unsafe { closedir(dirp) };
+
+        self.available_mice as i32
+    }
+    /* Opens every device that isn't open yet. */
+    fn scan_devices(&mut self) {
+        let files = match std::fs::read_dir("/dev/input") {
+            Ok(x) => x,
+            Err(x) => {
+                dbg!(x);
+                return;
+            }
+        };
         for file in files {
             let path = file.unwrap().path();
             let path: &OsStr = &path.as_os_str();
@@ -337,14 +1096,17 @@ impl DriverContainer {
                 bytes.push(0)
             }
             let cstr = CStr::from_bytes_with_nul(&bytes).unwrap();
-            if self.open_if_mouse(cstr) {
-                self.available_mice += 1;
+            if self.get_all_mice().any(|v| v.path.as_c_str() == cstr) {
+                continue;
+            }
+            if let Some(&ino) = self.rejected.get(cstr) {
+                if matches!(stat(cstr), Ok(x) if x.st_ino == ino) {
+                    continue;
+                }
+                self.rejected.remove(cstr);
             }
+            self.open_if_mouse(cstr);
         }
-
-        unsafe { closedir(dirp) };
-
-        self.available_mice as i32
     }
 
     pub(crate) fn linux_evdev_name(&self, index: usize) -> Option<&CStr> {
@@ -353,13 +1115,23 @@ impl DriverContainer {
             .and_then(|v| v.as_ref())
             .map(|v| unsafe { CStr::from_bytes_with_nul_unchecked(&v.name) })
     }
-    pub(crate) fn linux_evdev_poll(&mut self) -> Option<ManyMouseEvent> {
+    pub(crate) fn linux_evdev_poll(&mut self) -> Option<super::Event> {
         /*
          * (i) is static so we iterate through all mice round-robin. This
          *  prevents a chatty mouse from dominating the queue.
          */
         static mut I: usize = 2;
 
+        if !self.lost.is_empty()
+            && self
+                .last_scan
+                .map(|v| v.elapsed() >= RESCAN_INTERVAL)
+                .unwrap_or(true)
+        {
+            self.last_scan = Some(Instant::now());
+            self.scan_devices();
+        }
+
         if unsafe { I >= self.available_mice } {
             unsafe { I = 0 };
         }
@@ -367,10 +1139,14 @@ impl DriverContainer {
         while unsafe { I < self.available_mice } {
             let mouse = unsafe { &mut self.mice[I] };
             if let Some(mouse) = mouse {
-                if let Ok(res) = mouse.poll_mouse(unsafe { I }) {
-                    if res.is_some() {
-                        return res;
+                if let Ok(Some(event)) = mouse.poll_mouse(unsafe { I }) {
+                    /* the id stays taken, so the ids of everything else don't change. */
+                    if matches!(event.event, super::ManyMouseEvent::Disconnect) {
+                        if let Some(mouse) = unsafe { self.mice[I].take() } {
+                            self.lost.push((unsafe { I }, mouse.identity, mouse.phys));
+                        }
                     }
+                    return Some(event);
                 }
             }
             unsafe { I += 1 };
@@ -378,8 +1154,112 @@ impl DriverContainer {
 
         None
     }
+    pub(crate) fn set_absolute_to_relative(&mut self, enabled: bool) {
+        self.absolute_to_relative = enabled;
+        for mouse in self.mice.iter_mut().filter_map(|v| v.as_mut()) {
+            mouse.absolute_to_relative = enabled;
+            mouse.last_x = None;
+            mouse.last_y = None;
+        }
+    }
+    pub(crate) fn set_keyboards_enabled(&mut self, enabled: bool) {
+        if self.keyboards == enabled {
+            return;
+        }
+        self.keyboards = enabled;
+        if enabled {
+            /* the keyboards among them are wanted now. */
+            self.rejected.clear();
+            self.scan_devices();
+        } else {
+            /* they go like unplugged devices, so they get their ids back when turned on again. */
+            for mouse in self.mice.iter_mut().filter_map(|v| v.as_mut()) {
+                if mouse.kind == DeviceKind::Keyboard {
+                    mouse.close();
+                }
+            }
+        }
+    }
+    pub(crate) fn queue_state(&mut self, id: usize) -> bool {
+        match self.mice.get_mut(id).and_then(|v| v.as_mut()) {
+            Some(mouse) => {
+                mouse.resync();
+                true
+            }
+            None => false,
+        }
+    }
+    pub(crate) fn revoke_device(&mut self, id: usize) -> bool {
+        let mouse = match self.mice.get_mut(id).and_then(|v| v.as_mut()) {
+            Some(x) => x,
+            None => return false,
+        };
+        /* reading fails from now on, which poll_mouse turns into a Suspend. */
+        match unsafe { eviocrevoke(mouse.fd.as_raw_fd(), 0) } {
+            Ok(_) => {
+                mouse.revoked = true;
+                true
+            }
+            Err(x) => {
+                dbg!(x);
+                false
+            }
+        }
+    }
+    pub(crate) fn resume_device(&mut self, id: usize) -> bool {
+        let mouse = match self.mice.get_mut(id).and_then(|v| v.as_mut()) {
+            Some(x) if x.suspended => x,
+            _ => return false,
+        };
+        let fd = unsafe { open(mouse.path.as_ptr(), O_RDONLY | O_NONBLOCK) };
+        if fd == -1 {
+            return false;
+        }
+        mouse.fd = unsafe { File::from_raw_fd(fd) };
+        set_clock(&mouse.fd, self.clock);
+        mouse.suspended = false;
+        mouse.revoked = false;
+        mouse.gone = false;
+        mouse.dropped = false;
+        mouse.raw_read = None;
+        mouse.read_buf.clear();
+        mouse.read_pos = 0;
+        mouse.pending.push_back(super::ManyMouseEvent::Resume);
+        /* everything got released on suspend, so this brings back whatever is held now. */
+        mouse.resync();
+        true
+    }
+    pub(crate) fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        for mouse in self.mice.iter_mut().filter_map(|v| v.as_mut()) {
+            set_clock(&mouse.fd, clock);
+            /* switching clocks throws away whatever the kernel still had queued, so does this. */
+            mouse.read_buf.clear();
+            mouse.read_pos = 0;
+            mouse.resync();
+        }
+    }
+    pub(crate) fn set_device_kind(&mut self, id: usize, kind: DeviceKind) -> bool {
+        match self.mice.get_mut(id).and_then(|v| v.as_mut()) {
+            Some(mouse) => {
+                mouse.kind = kind;
+                true
+            }
+            None => false,
+        }
+    }
+    pub(crate) fn set_raw_mode(&mut self, mode: RawMode) {
+        self.raw_mode = mode;
+        for mouse in self.mice.iter_mut().filter_map(|v| v.as_mut()) {
+            mouse.raw_mode = mode;
+        }
+    }
     pub(crate) fn get_all_ids(&self) -> impl Iterator<Item = usize> + '_ {
-        self.get_all_mice().enumerate().map(|(i, _)| i)
+        self.mice[0..self.available_mice]
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_some())
+            .map(|(i, _)| i)
     }
     pub(crate) fn get_all_mice<'a>(&'a self) -> impl Iterator<Item = &MouseStruct> + 'a {
         self.mice[0..self.available_mice]
@@ -390,6 +1270,13 @@ impl DriverContainer {
         let mut new_self = Self {
             mice: Default::default(), //[None; MAX_MICE],
             available_mice: 0,
+            absolute_to_relative: false,
+            keyboards: false,
+            raw_mode: RawMode::Off,
+            lost: Vec::new(),
+            rejected: HashMap::new(),
+            last_scan: None,
+            clock: Clock::Monotonic,
         };
         new_self.linux_evdev_init();
         new_self
@@ -398,42 +1285,7 @@ impl DriverContainer {
 
 impl Driver for DriverContainer {
     fn poll(&mut self) -> Option<super::Event> {
-        self.linux_evdev_poll().map(|v| super::Event {
-            device_id: v.device,
-            event: match v.event_type {
-                ManyMouseEventType::Absmotion => super::ManyMouseEvent::AbsoluteMotion {
-                    moved: if v.item == 0 {
-                        super::AbsoluteMotionMoved::X(v.value)
-                    } else {
-                        super::AbsoluteMotionMoved::Y(v.value)
-                    },
-                    max: v.maxval.unwrap(),
-                    min: v.minval.unwrap(),
-                },
-                ManyMouseEventType::Relmotion => super::ManyMouseEvent::RelativeMotion {
-                    x: if v.item == 0 { v.value } else { 0 },
-                    y: if v.item == 0 { 0 } else { v.value },
-                },
-                ManyMouseEventType::Button => {
-                    println!("{:?}", v);
-                    super::ManyMouseEvent::Button {
-                        side: if v.item == 1 {
-                            super::Button::Left
-                        } else {
-                            super::Button::Right
-                        },
-                        is_pressed: v.value == 1,
-                    }
-                }
-                ManyMouseEventType::Scroll => super::ManyMouseEvent::Scroll {
-                    value: v.value,
-                    min: v.minval.unwrap(),
-                    max: v.maxval.unwrap(),
-                },
-                ManyMouseEventType::_Disconnect => super::ManyMouseEvent::Disconnect,
-                ManyMouseEventType::_Max => super::ManyMouseEvent::Max {},
-            },
-        })
+        self.linux_evdev_poll()
     }
 
     fn driver_name(&self) -> Cow<'static, str> {
@@ -444,6 +1296,17 @@ impl Driver for DriverContainer {
         self.linux_evdev_name(id)
     }
 
+    fn device_kind(&self, id: usize) -> Option<DeviceKind> {
+        self.mice.get(id).and_then(|v| v.as_ref()).map(|v| v.kind)
+    }
+
+    fn device_identity(&self, id: usize) -> Option<DeviceIdentity> {
+        self.mice
+            .get(id)
+            .and_then(|v| v.as_ref())
+            .map(|v| v.identity.clone())
+    }
+
     fn get_all_mouse_names(&self) -> Box<dyn Iterator<Item = &CStr> + '_> {
         Box::new(
             self.get_all_mice()
@@ -454,4 +1317,85 @@ impl Driver for DriverContainer {
     fn get_all_ids(&self) -> Box<dyn Iterator<Item = usize> + '_> {
         Box::new(self.get_all_ids())
     }
+
+    fn set_absolute_to_relative(&mut self, enabled: bool) -> bool {
+        self.set_absolute_to_relative(enabled);
+        true
+    }
+
+    fn set_keyboards_enabled(&mut self, enabled: bool) -> bool {
+        self.set_keyboards_enabled(enabled);
+        true
+    }
+
+    fn device_state(&self, id: usize) -> Option<DeviceState> {
+        self.mice
+            .get(id)
+            .and_then(|v| v.as_ref())
+            .map(|v| v.initial_state.clone())
+    }
+
+    fn queue_state(&mut self, id: usize) -> bool {
+        self.queue_state(id)
+    }
+
+    fn set_raw_mode(&mut self, mode: RawMode) -> bool {
+        self.set_raw_mode(mode);
+        true
+    }
+
+    fn set_device_kind(&mut self, id: usize, kind: DeviceKind) -> bool {
+        self.set_device_kind(id, kind)
+    }
+
+    fn revoke_device(&mut self, id: usize) -> bool {
+        self.revoke_device(id)
+    }
+
+    fn set_clock(&mut self, clock: Clock) -> bool {
+        self.set_clock(clock);
+        true
+    }
+
+    fn resume_device(&mut self, id: usize) -> bool {
+        self.resume_device(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(sec: time_t, usec: suseconds_t, type_: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(EVENT_SIZE);
+        bytes.extend_from_slice(&sec.to_ne_bytes());
+        bytes.extend_from_slice(&usec.to_ne_bytes());
+        bytes.extend_from_slice(&type_.to_ne_bytes());
+        bytes.extend_from_slice(&code.to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decode_event() {
+        let bytes = encode(1234, 567_890, 2, 1, -42);
+        assert_eq!(bytes.len(), EVENT_SIZE);
+        let event = decode(&bytes);
+        assert_eq!(event.time.tv_sec, 1234);
+        assert_eq!(event.time.tv_usec, 567_890);
+        assert_eq!(event.type_, 2);
+        assert_eq!(event.code, 1);
+        assert_eq!(event.value, -42);
+    }
+
+    #[test]
+    fn decode_batch() {
+        let mut bytes = encode(1, 0, EV_REL as u16, REL_X as u16, 5);
+        bytes.extend(encode(1, 10, EV_SYN as u16, SYN_REPORT as u16, 0));
+        let events: Vec<_> = bytes.chunks_exact(EVENT_SIZE).map(decode).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].value, 5);
+        assert_eq!(events[1].time.tv_usec, 10);
+        assert_eq!(events[1].type_, EV_SYN as u16);
+    }
 }
@@ -0,0 +1,193 @@
+use std::{collections::VecDeque, convert::TryInto, fs::File, os::unix::prelude::AsRawFd};
+
+use evdev::raw::{eviocgmtslots, input_absinfo};
+use input_linux_sys::{
+    input_event, ABS_MT_POSITION_X, ABS_MT_POSITION_Y, ABS_MT_SLOT, ABS_MT_TRACKING_ID, ABS_X,
+    ABS_Y, EV_ABS,
+};
+
+use super::{absinfo, synthetic};
+use crate::{drivers::ManyMouseEvent, frame::AbsoluteAxis};
+
+/* nothing real comes close to this, it only guards against silly absinfo. */
+const MAX_SLOTS: usize = 64;
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    tracking_id: Option<i32>,
+    reported_id: Option<i32>,
+    x: i32,
+    y: i32,
+    changed: bool,
+}
+
+/// Keeps track of the contacts of a device that speaks multitouch protocol B.
+pub struct MultiTouch {
+    slots: Vec<Slot>,
+    current: usize,
+    x: input_absinfo,
+    y: input_absinfo,
+    /* a device without multitouch, its only contact comes from ABS_X, ABS_Y and BTN_TOUCH. */
+    single: bool,
+    next_id: i32,
+}
+
+impl MultiTouch {
+    pub fn new(fd: &File) -> Option<Self> {
+        let slot = absinfo(fd, ABS_MT_SLOT)?;
+        let x = absinfo(fd, ABS_MT_POSITION_X)?;
+        let y = absinfo(fd, ABS_MT_POSITION_Y)?;
+        let count = (slot.maximum.max(0) as usize + 1).min(MAX_SLOTS);
+        Some(Self {
+            slots: vec![Slot::default(); count],
+            current: (slot.value.max(0) as usize).min(count - 1),
+            x,
+            y,
+            single: false,
+            next_id: 0,
+        })
+    }
+
+    /// Reports the one contact of a device that can only tell a single touch apart.
+    pub fn single_touch(fd: &File) -> Option<Self> {
+        Some(Self {
+            slots: vec![Slot::default()],
+            current: 0,
+            x: absinfo(fd, ABS_X)?,
+            y: absinfo(fd, ABS_Y)?,
+            single: true,
+            next_id: 0,
+        })
+    }
+
+    /// Returns false if this wasn't a multitouch event.
+    pub fn handle(&mut self, event: &input_event) -> bool {
+        let code = i32::from(event.code);
+        if self.single {
+            /* the position is still needed for AbsoluteMotion, so it never counts as handled. */
+            let slot = &mut self.slots[0];
+            if code == ABS_X {
+                slot.x = event.value;
+                slot.changed = true;
+            } else if code == ABS_Y {
+                slot.y = event.value;
+                slot.changed = true;
+            }
+            return false;
+        }
+        if code == ABS_MT_SLOT {
+            self.current = (event.value.max(0) as usize).min(self.slots.len() - 1);
+            return true;
+        }
+        let slot = &mut self.slots[self.current];
+        if code == ABS_MT_TRACKING_ID {
+            slot.tracking_id = if event.value < 0 {
+                None
+            } else {
+                Some(event.value)
+            };
+        } else if code == ABS_MT_POSITION_X {
+            slot.x = event.value;
+        } else if code == ABS_MT_POSITION_Y {
+            slot.y = event.value;
+        } else {
+            return false;
+        }
+        slot.changed = true;
+        true
+    }
+
+    /// Follows BTN_TOUCH, only needed without multitouch.
+    pub fn handle_touch(&mut self, is_pressed: bool) {
+        if !self.single {
+            return;
+        }
+        let slot = &mut self.slots[0];
+        slot.tracking_id = if is_pressed {
+            /* every touch gets its own id, like the ones of ABS_MT_TRACKING_ID. */
+            self.next_id = self.next_id.wrapping_add(1) & i32::MAX;
+            Some(self.next_id)
+        } else {
+            None
+        };
+        slot.changed = true;
+    }
+
+    /// Queues the state of every slot as the kernel has it now, after events got dropped.
+    pub fn resync(&self, fd: &File, events: &mut VecDeque<input_event>) {
+        if self.single {
+            /* BTN_TOUCH, ABS_X and ABS_Y get resynced like on any other device. */
+            return;
+        }
+        let count = self.slots.len();
+        let mut values = Vec::new();
+        for code in [ABS_MT_TRACKING_ID, ABS_MT_POSITION_X, ABS_MT_POSITION_Y].iter() {
+            /* struct input_mt_request_layout: the code, followed by one value per slot. */
+            let mut buf = vec![0u8; 4 * (count + 1)];
+            buf[..4].copy_from_slice(&(*code as u32).to_ne_bytes());
+            if let Err(x) = unsafe { eviocgmtslots(fd.as_raw_fd(), &mut buf) } {
+                dbg!(x);
+                return;
+            }
+            let slots: Vec<i32> = buf[4..]
+                .chunks_exact(4)
+                .map(|v| i32::from_ne_bytes(v.try_into().unwrap()))
+                .collect();
+            values.push((*code, slots));
+        }
+        for slot in 0..count {
+            events.push_back(synthetic(EV_ABS, ABS_MT_SLOT, slot as i32));
+            for (code, slots) in &values {
+                events.push_back(synthetic(EV_ABS, *code, slots[slot]));
+            }
+        }
+        let current = absinfo(fd, ABS_MT_SLOT).map(|v| v.value).unwrap_or(0);
+        events.push_back(synthetic(EV_ABS, ABS_MT_SLOT, current));
+    }
+
+    /// Queues the lifting of every contact, for when the device goes away.
+    pub fn lift_all(&self, events: &mut VecDeque<input_event>) {
+        if self.single {
+            return;
+        }
+        for slot in 0..self.slots.len() {
+            events.push_back(synthetic(EV_ABS, ABS_MT_SLOT, slot as i32));
+            events.push_back(synthetic(EV_ABS, ABS_MT_TRACKING_ID, -1));
+        }
+    }
+
+    /// Turns everything that changed since the last SYN_REPORT into touch events.
+    pub fn report(&mut self, pending: &mut VecDeque<ManyMouseEvent>) {
+        let (x_info, y_info) = (self.x, self.y);
+        for slot in self.slots.iter_mut().filter(|v| v.changed) {
+            slot.changed = false;
+            let x = AbsoluteAxis {
+                value: slot.x,
+                min: x_info.minimum,
+                max: x_info.maximum,
+            }
+            .normalized();
+            let y = AbsoluteAxis {
+                value: slot.y,
+                min: y_info.minimum,
+                max: y_info.maximum,
+            }
+            .normalized();
+            match (slot.reported_id, slot.tracking_id) {
+                (Some(old), Some(new)) if old == new => {
+                    pending.push_back(ManyMouseEvent::TouchMove { contact: new, x, y })
+                }
+                (old, new) => {
+                    /* a slot can go straight to a new contact if events got merged. */
+                    if let Some(contact) = old {
+                        pending.push_back(ManyMouseEvent::TouchUp { contact });
+                    }
+                    if let Some(contact) = new {
+                        pending.push_back(ManyMouseEvent::TouchDown { contact, x, y });
+                    }
+                }
+            }
+            slot.reported_id = slot.tracking_id;
+        }
+    }
+}
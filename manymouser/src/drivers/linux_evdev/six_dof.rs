@@ -0,0 +1,79 @@
+use std::{collections::VecDeque, convert::TryInto};
+
+use input_linux_sys::{
+    input_event, ABS_RZ, ABS_X, BTN_GAMEPAD, BTN_JOYSTICK, EV_ABS, EV_REL,
+    INPUT_PROP_ACCELEROMETER, REL_RZ, REL_X,
+};
+
+use super::test_bit;
+use crate::drivers::ManyMouseEvent;
+
+/* X, Y, Z, RX, RY and RZ follow each other, both for EV_REL and EV_ABS. */
+const AXES: usize = 6;
+
+/// Collects the six axes of a 3D mouse into one event per SYN_REPORT.
+pub struct SixDof {
+    /* relative devices report how far they moved since the last report, so start over. */
+    relative: bool,
+    values: [i32; AXES],
+    changed: bool,
+}
+
+impl SixDof {
+    /// Returns `None` if the device doesn't have all six axes, either relative or absolute.
+    pub fn new(relcaps: &[u8], abscaps: &[u8], keycaps: &[u8], propcaps: &[u8]) -> Option<Self> {
+        let relative = (REL_X..=REL_RZ).all(|v| test_bit(relcaps, v) != 0);
+        /* gamepads have the same six absolute axes, but they come with gamepad buttons.
+         * their motion sensors have no buttons at all, the kernel marks those as accelerometers. */
+        let absolute = (ABS_X..=ABS_RZ).all(|v| test_bit(abscaps, v) != 0)
+            && test_bit(keycaps, BTN_GAMEPAD) == 0
+            && test_bit(keycaps, BTN_JOYSTICK) == 0
+            && test_bit(propcaps, INPUT_PROP_ACCELEROMETER) == 0;
+        if !relative && !absolute {
+            return None;
+        }
+        Some(Self {
+            relative,
+            values: [0; AXES],
+            changed: false,
+        })
+    }
+
+    /// Returns false if this wasn't one of the six axes.
+    pub fn handle(&mut self, event: &input_event) -> bool {
+        let wanted = if self.relative { EV_REL } else { EV_ABS };
+        if event.type_ != wanted.try_into().unwrap() {
+            return false;
+        }
+        let axis = usize::from(event.code);
+        if axis >= AXES {
+            return false;
+        }
+        if self.relative {
+            self.values[axis] += event.value;
+        } else {
+            self.values[axis] = event.value;
+        }
+        self.changed = true;
+        true
+    }
+
+    pub fn report(&mut self, pending: &mut VecDeque<ManyMouseEvent>) {
+        if !self.changed {
+            return;
+        }
+        let [tx, ty, tz, rx, ry, rz] = self.values;
+        pending.push_back(ManyMouseEvent::Motion6Dof {
+            tx,
+            ty,
+            tz,
+            rx,
+            ry,
+            rz,
+        });
+        if self.relative {
+            self.values = [0; AXES];
+        }
+        self.changed = false;
+    }
+}
@@ -0,0 +1,144 @@
+use std::{collections::VecDeque, fs::File};
+
+use evdev::raw::input_absinfo;
+use input_linux_sys::{
+    input_event, ABS_DISTANCE, ABS_PRESSURE, ABS_TILT_X, ABS_TILT_Y, BTN_TOOL_PEN, BTN_TOOL_RUBBER,
+};
+
+use super::{absinfo, test_bit};
+use crate::{
+    drivers::{ManyMouseEvent, PenTool},
+    frame::AbsoluteAxis,
+};
+
+/// Keeps track of the pen of a graphics tablet, the position itself is left to ABS_X and ABS_Y.
+pub struct Tablet {
+    /* absinfo.value always holds the latest value of the axis. */
+    pressure: Option<input_absinfo>,
+    distance: Option<input_absinfo>,
+    tilt_x: Option<input_absinfo>,
+    tilt_y: Option<input_absinfo>,
+    tool: Option<PenTool>,
+    reported_tool: Option<PenTool>,
+    changed: bool,
+}
+
+fn axis(fd: &File, abscaps: &[u8], code: i32) -> Option<input_absinfo> {
+    if test_bit(abscaps, code) == 0 {
+        return None;
+    }
+    absinfo(fd, code)
+}
+
+fn normalized(info: Option<input_absinfo>) -> f32 {
+    match info {
+        Some(info) => AbsoluteAxis {
+            value: info.value,
+            min: info.minimum,
+            max: info.maximum,
+        }
+        .normalized(),
+        None => 0.0,
+    }
+}
+
+/* tilt is centered on 0 even when the range isn't, so scale each side on its own. */
+fn tilt(info: Option<input_absinfo>) -> f32 {
+    let info = match info {
+        Some(x) => x,
+        None => return 0.0,
+    };
+    let range = if info.value < 0 {
+        -info.minimum
+    } else {
+        info.maximum
+    };
+    if range <= 0 {
+        return 0.0;
+    }
+    (info.value as f32 / range as f32).clamp(-1.0, 1.0)
+}
+
+impl Tablet {
+    pub fn new(fd: &File, abscaps: &[u8]) -> Self {
+        Self {
+            pressure: axis(fd, abscaps, ABS_PRESSURE),
+            distance: axis(fd, abscaps, ABS_DISTANCE),
+            tilt_x: axis(fd, abscaps, ABS_TILT_X),
+            tilt_y: axis(fd, abscaps, ABS_TILT_Y),
+            tool: None,
+            reported_tool: None,
+            changed: false,
+        }
+    }
+
+    /// Returns false if this wasn't one of the pen axes.
+    pub fn handle_abs(&mut self, event: &input_event) -> bool {
+        let code = i32::from(event.code);
+        let info = if code == ABS_PRESSURE {
+            &mut self.pressure
+        } else if code == ABS_DISTANCE {
+            &mut self.distance
+        } else if code == ABS_TILT_X {
+            &mut self.tilt_x
+        } else if code == ABS_TILT_Y {
+            &mut self.tilt_y
+        } else {
+            return false;
+        };
+        if let Some(info) = info {
+            info.value = event.value;
+            self.changed = true;
+        }
+        true
+    }
+
+    /// Returns false if this wasn't a tool coming into or leaving proximity.
+    pub fn handle_key(&mut self, event: &input_event) -> bool {
+        let code = i32::from(event.code);
+        let tool = if code == BTN_TOOL_PEN {
+            PenTool::Pen
+        } else if code == BTN_TOOL_RUBBER {
+            PenTool::Eraser
+        } else {
+            return false;
+        };
+        if event.value != 0 {
+            self.tool = Some(tool);
+        } else if self.tool == Some(tool) {
+            self.tool = None;
+        }
+        true
+    }
+
+    /// Turns everything that changed since the last SYN_REPORT into pen events.
+    pub fn report(&mut self, pending: &mut VecDeque<ManyMouseEvent>) {
+        if self.tool != self.reported_tool {
+            if let Some(tool) = self.reported_tool {
+                pending.push_back(ManyMouseEvent::PenProximity {
+                    tool,
+                    in_proximity: false,
+                });
+            }
+            if let Some(tool) = self.tool {
+                pending.push_back(ManyMouseEvent::PenProximity {
+                    tool,
+                    in_proximity: true,
+                });
+                /* so the axes of a pen that just arrived are known right away. */
+                self.changed = true;
+            }
+            self.reported_tool = self.tool;
+        }
+        if let (true, Some(tool)) = (self.changed, self.tool) {
+            pending.push_back(ManyMouseEvent::PenMotion {
+                tool,
+                pressure: normalized(self.pressure),
+                distance: normalized(self.distance),
+                tilt_x: tilt(self.tilt_x),
+                tilt_y: tilt(self.tilt_y),
+            });
+        }
+        self.changed = false;
+    }
+}
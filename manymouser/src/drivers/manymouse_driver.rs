@@ -1,4 +1,10 @@
-use std::{borrow::Cow, convert::TryInto, ffi::CStr, mem::MaybeUninit};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    ffi::CStr,
+    mem::MaybeUninit,
+};
 
 use bindgen::{
     ManyMouseEventType_MANYMOUSE_EVENT_ABSMOTION, ManyMouseEventType_MANYMOUSE_EVENT_BUTTON,
@@ -7,29 +13,44 @@ use bindgen::{
     ManyMouse_Quit,
 };
 
+use crate::absolute::AbsoluteTracker;
+
 use super::{
     bindgen::{self, ManyMouse_Init},
-    AbsoluteMotionMoved, Button, Driver, ManyMouseEvent,
+    AbsoluteMotionMoved, Button, DeviceKind, Driver, ManyMouseEvent, ScrollAxis, SCROLL_DETENT,
 };
 pub(crate) struct ManyMouseDriver {
     max_mice: i32,
+    /* buttons that are down, so they can be released when their device goes away. */
+    held: HashMap<usize, Vec<Button>>,
+    pending: VecDeque<super::Event>,
+    absolute: AbsoluteTracker,
+    /* read while looking for the Y that goes with an X, but it wasn't one. */
+    unread: Option<super::Event>,
+    /* the XInput2 backend sends button 6, scrolling left, as a positive horizontal scroll. */
+    flip_horizontal: bool,
 }
 
 impl ManyMouseDriver {
     pub(crate) fn new() -> Option<Self> {
         let max_mice = unsafe { ManyMouse_Init() };
         println!("{}", max_mice);
-        (max_mice > 0).then(|| Self { max_mice })
+        (max_mice > 0).then(|| {
+            let mut driver = Self {
+                max_mice,
+                held: HashMap::new(),
+                pending: VecDeque::new(),
+                absolute: AbsoluteTracker::default(),
+                unread: None,
+                flip_horizontal: false,
+            };
+            driver.flip_horizontal = driver.driver_name() == "X11 XInput2 extension";
+            driver
+        })
     }
-}
 
-impl Drop for ManyMouseDriver {
-    fn drop(&mut self) {
-        unsafe { ManyMouse_Quit() }
-    }
-}
-impl Driver for ManyMouseDriver {
-    fn poll(&mut self) -> Option<super::Event> {
+    fn read_event(&mut self) -> Option<super::Event> {
+        let flip_horizontal = self.flip_horizontal;
         unsafe {
             let mut event = MaybeUninit::zeroed();
             let success = bindgen::ManyMouse_PollEvent(event.as_mut_ptr());
@@ -41,6 +62,7 @@ impl Driver for ManyMouseDriver {
         }
         .map(|v| super::Event {
             device_id: v.device as usize,
+            timestamp: None,
             event: match v.type_ {
                 ManyMouseEventType_MANYMOUSE_EVENT_ABSMOTION => ManyMouseEvent::AbsoluteMotion {
                     max: v.maxval,
@@ -56,13 +78,7 @@ impl Driver for ManyMouseDriver {
                     y: if v.item == 0 { 0 } else { v.value },
                 },
                 ManyMouseEventType_MANYMOUSE_EVENT_BUTTON => ManyMouseEvent::Button {
-                    side: if v.item == 1 {
-                        Button::Left
-                    } else if v.item == 2 {
-                        Button::Right
-                    } else {
-                        Button::Middle
-                    },
+                    side: Button::from_item(v.item),
                     is_pressed: v.value == 1,
                 },
                 ManyMouseEventType_MANYMOUSE_EVENT_DISCONNECT => ManyMouseEvent::Disconnect,
@@ -71,14 +87,92 @@ impl Driver for ManyMouseDriver {
                     ManyMouseEvent::Max {}
                 }
                 ManyMouseEventType_MANYMOUSE_EVENT_SCROLL => ManyMouseEvent::Scroll {
-                    value: v.value,
-                    min: v.minval,
-                    max: v.maxval,
+                    axis: if v.item == 0 {
+                        ScrollAxis::Vertical
+                    } else {
+                        ScrollAxis::Horizontal
+                    },
+                    /* manymouse only reports whole detents */
+                    value: if v.item == 1 && flip_horizontal {
+                        -v.value * SCROLL_DETENT
+                    } else {
+                        v.value * SCROLL_DETENT
+                    },
                 },
                 x => panic!("Got invalid event type {}", x),
             },
         })
     }
+}
+
+impl Drop for ManyMouseDriver {
+    fn drop(&mut self) {
+        unsafe { ManyMouse_Quit() }
+    }
+}
+
+impl Driver for ManyMouseDriver {
+    fn poll(&mut self) -> Option<super::Event> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let event = match self.unread.take() {
+            Some(x) => x,
+            None => self.read_event()?,
+        };
+        let id = event.device_id;
+        match event.event {
+            ManyMouseEvent::Button { side, is_pressed } => {
+                let held = self.held.entry(id).or_default();
+                held.retain(|v| *v != side);
+                if is_pressed {
+                    held.push(side);
+                }
+            }
+            ManyMouseEvent::Disconnect => {
+                self.absolute.track(&event);
+                for side in self.held.remove(&id).unwrap_or_default() {
+                    self.pending.push_back(super::Event {
+                        device_id: id,
+                        timestamp: event.timestamp,
+                        event: ManyMouseEvent::Button {
+                            side,
+                            is_pressed: false,
+                        },
+                    });
+                }
+                self.pending.push_back(event);
+                return self.pending.pop_front();
+            }
+            ManyMouseEvent::AbsoluteMotion { .. } => {
+                /* manymouse doesn't say where a report ends, so every axis makes a position. */
+                self.absolute.track(&event);
+                if let Some((x, y)) = self.absolute.position(id) {
+                    self.pending.push_back(super::Event {
+                        device_id: id,
+                        timestamp: event.timestamp,
+                        event: ManyMouseEvent::NormalizedAbsoluteMotion { x, y },
+                    });
+                }
+            }
+            ManyMouseEvent::RelativeMotion { x, y: 0 } => {
+                /* manymouse hands out the X and Y of one motion one after the other. */
+                if let Some(next) = self.read_event() {
+                    match next.event {
+                        ManyMouseEvent::RelativeMotion { x: 0, y } if next.device_id == id => {
+                            return Some(super::Event {
+                                event: ManyMouseEvent::RelativeMotion { x, y },
+                                ..event
+                            });
+                        }
+                        _ => self.unread = Some(next),
+                    }
+                }
+            }
+            _ => {}
+        }
+        Some(event)
+    }
 
     fn driver_name(&self) -> Cow<'static, str> {
         let name_ptr = unsafe { bindgen::ManyMouse_DriverName() };
@@ -102,6 +196,11 @@ impl Driver for ManyMouseDriver {
         }
     }
 
+    fn device_kind(&self, id: usize) -> Option<DeviceKind> {
+        /* manymouse doesn't tell us more than this. */
+        self.device_name(id).map(|_| DeviceKind::Mouse)
+    }
+
     fn get_all_mouse_names(&self) -> Box<dyn Iterator<Item = &CStr> + '_> {
         Box::new(
             self.get_all_ids()
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use crate::drivers::{AbsoluteMotionMoved, Button, Event, ManyMouseEvent, ScrollAxis};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbsoluteAxis {
+    pub value: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl AbsoluteAxis {
+    /// Maps `value` into 0.0..=1.0 based on this axis' min and max.
+    pub fn normalized(&self) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        let range = i64::from(self.max) - i64::from(self.min);
+        let value = i64::from(self.value) - i64::from(self.min);
+        (value as f64 / range as f64).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonTransition {
+    pub side: Button,
+    pub is_pressed: bool,
+}
+
+/// Everything a single device did since the previous call to `Context::frame`.
+#[derive(Clone, Debug, Default)]
+pub struct DeviceFrame {
+    pub relative_x: i32,
+    pub relative_y: i32,
+    /// In 1/120ths of a detent, like `ManyMouseEvent::Scroll`.
+    pub scroll_vertical: i32,
+    pub scroll_horizontal: i32,
+    pub dial: i32,
+    /// Button presses and releases, in the order they happened.
+    pub buttons: Vec<ButtonTransition>,
+    /// Last known absolute position. Unlike the other fields this is kept between frames.
+    pub absolute_x: Option<AbsoluteAxis>,
+    pub absolute_y: Option<AbsoluteAxis>,
+    /// Last known position when normalized absolute motion is turned on. Also kept between frames.
+    pub normalized_position: Option<(f32, f32)>,
+    pub disconnected: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Frame {
+    devices: HashMap<usize, DeviceFrame>,
+}
+
+impl Frame {
+    pub fn device(&self, id: usize) -> Option<&DeviceFrame> {
+        self.devices.get(&id)
+    }
+    pub fn devices(&self) -> impl Iterator<Item = (usize, &DeviceFrame)> + '_ {
+        self.devices.iter().map(|(id, frame)| (*id, frame))
+    }
+
+    pub(crate) fn begin(&mut self) {
+        self.devices.retain(|_, frame| !frame.disconnected);
+        for frame in self.devices.values_mut() {
+            frame.relative_x = 0;
+            frame.relative_y = 0;
+            frame.scroll_vertical = 0;
+            frame.scroll_horizontal = 0;
+            frame.dial = 0;
+            frame.buttons.clear();
+        }
+    }
+
+    pub(crate) fn add_event(&mut self, event: &Event) {
+        let frame = self.devices.entry(event.device_id).or_default();
+        match event.event {
+            ManyMouseEvent::Button { side, is_pressed } => {
+                frame.buttons.push(ButtonTransition { side, is_pressed })
+            }
+            ManyMouseEvent::RelativeMotion { x, y } => {
+                frame.relative_x += x;
+                frame.relative_y += y;
+            }
+            ManyMouseEvent::AbsoluteMotion { moved, min, max } => match moved {
+                AbsoluteMotionMoved::X(value) => {
+                    frame.absolute_x = Some(AbsoluteAxis { value, min, max })
+                }
+                AbsoluteMotionMoved::Y(value) => {
+                    frame.absolute_y = Some(AbsoluteAxis { value, min, max })
+                }
+            },
+            ManyMouseEvent::NormalizedAbsoluteMotion { x, y }
+            | ManyMouseEvent::GunAim { x, y, .. } => frame.normalized_position = Some((x, y)),
+            ManyMouseEvent::Scroll { axis, value } => match axis {
+                ScrollAxis::Vertical => frame.scroll_vertical += value,
+                ScrollAxis::Horizontal => frame.scroll_horizontal += value,
+            },
+            ManyMouseEvent::Dial { delta } => frame.dial += delta,
+            ManyMouseEvent::TouchDown { .. }
+            | ManyMouseEvent::TouchMove { .. }
+            | ManyMouseEvent::TouchUp { .. }
+            | ManyMouseEvent::PenProximity { .. }
+            | ManyMouseEvent::PenMotion { .. }
+            | ManyMouseEvent::Motion6Dof { .. }
+            | ManyMouseEvent::Key { .. }
+            | ManyMouseEvent::Raw { .. }
+            | ManyMouseEvent::Suspend
+            | ManyMouseEvent::Resume => {}
+            ManyMouseEvent::Disconnect => frame.disconnected = true,
+            ManyMouseEvent::Reconnect => frame.disconnected = false,
+            ManyMouseEvent::Max {} => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis(value: i32, min: i32, max: i32) -> f32 {
+        AbsoluteAxis { value, min, max }.normalized()
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!(axis(0, 0, 100), 0.0);
+        assert_eq!(axis(50, 0, 100), 0.5);
+        assert_eq!(axis(100, 0, 100), 1.0);
+        assert_eq!(axis(0, -512, 511), 512.0 / 1023.0);
+    }
+
+    #[test]
+    fn normalized_out_of_range() {
+        assert_eq!(axis(-10, 0, 100), 0.0);
+        assert_eq!(axis(110, 0, 100), 1.0);
+        assert_eq!(axis(i32::MAX, i32::MIN, i32::MAX), 1.0);
+        /* axes without a range can't say anything. */
+        assert_eq!(axis(5, 10, 10), 0.0);
+        assert_eq!(axis(5, 10, 0), 0.0);
+    }
+}
@@ -1,8 +1,27 @@
+mod absolute;
+mod acceleration;
+mod calibration;
+mod cursor;
 mod drivers;
+mod frame;
+mod scroll;
+mod timestamps;
+mod touchpad;
 
-use std::{borrow::Cow, ffi::CStr, marker::PhantomData};
+use std::{borrow::Cow, collections::HashMap, ffi::CStr, marker::PhantomData};
 
-use drivers::{Driver, Event};
+use absolute::AbsoluteTracker;
+pub use acceleration::{AccelProfile, AcceleratedMotion, Acceleration};
+pub use calibration::{Calibration, CalibrationStore, ReferencePoint};
+pub use cursor::{Bounds, Cursors, EdgeBehavior};
+use drivers::Driver;
+pub use drivers::{
+    AbsoluteMotionMoved, Button, Clock, DeviceIdentity, DeviceKind, DeviceState, Event, KeyState,
+    ManyMouseEvent, PenTool, RawMode, ScrollAxis, SCROLL_DETENT,
+};
+pub use frame::{AbsoluteAxis, ButtonTransition, DeviceFrame, Frame};
+pub use scroll::{ScrollAmount, ScrollProcessor};
+pub use touchpad::TouchpadGestures;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum DriverOptions {
@@ -28,17 +47,82 @@ fn get_driver(options: &[DriverOptions]) -> Option<Box<dyn Driver>> {
 
 pub struct Context {
     driver: Box<dyn Driver>,
+    frame: Frame,
+    normalized_absolute: bool,
+    absolute: AbsoluteTracker,
+    touch_matrices: HashMap<usize, [f32; 6]>,
+    calibrations: HashMap<usize, Calibration>,
     _not_send_or_sync: PhantomData<*mut ()>,
 }
 impl Context {
     pub fn new(preference: &[DriverOptions]) -> Option<Self> {
         Some(Self {
             driver: get_driver(preference)?,
+            frame: Frame::default(),
+            normalized_absolute: false,
+            absolute: AbsoluteTracker::default(),
+            touch_matrices: HashMap::new(),
+            calibrations: HashMap::new(),
             _not_send_or_sync: PhantomData,
         })
     }
     pub fn poll(&mut self) -> Option<Event> {
-        self.driver.poll()
+        loop {
+            let event = self.driver.poll()?;
+            if let Some(event) = self.process(event) {
+                return Some(event);
+            }
+        }
+    }
+    fn process(&mut self, mut event: Event) -> Option<Event> {
+        if let Some(matrix) = self.touch_matrices.get(&event.device_id) {
+            if let ManyMouseEvent::TouchDown { x, y, .. } | ManyMouseEvent::TouchMove { x, y, .. } =
+                &mut event.event
+            {
+                let (old_x, old_y) = (*x, *y);
+                *x = matrix[0] * old_x + matrix[1] * old_y + matrix[2];
+                *y = matrix[3] * old_x + matrix[4] * old_y + matrix[5];
+            }
+        }
+        self.absolute.track(&event);
+        let calibration = self.calibrations.get(&event.device_id);
+        if let ManyMouseEvent::GunAim { x, y, .. } = event.event {
+            let (x, y) = match calibration {
+                Some(calibration) => calibration.apply(x, y),
+                None => (x, y),
+            };
+            let on_screen = (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y);
+            event.event = ManyMouseEvent::GunAim { x, y, on_screen };
+            return Some(event);
+        }
+        /* the separate axes of a gun only count for absolute_position, its aim is in GunAim. */
+        if let ManyMouseEvent::AbsoluteMotion { .. } = event.event {
+            if self.driver.device_kind(event.device_id) == Some(DeviceKind::LightGun) {
+                return None;
+            }
+        }
+        /* drivers report both, the normalized one once X and Y of a report are complete. */
+        let normalized = self.normalized_absolute || calibration.is_some();
+        match event.event {
+            ManyMouseEvent::AbsoluteMotion { .. } if normalized => None,
+            ManyMouseEvent::NormalizedAbsoluteMotion { .. } if !normalized => None,
+            ManyMouseEvent::NormalizedAbsoluteMotion { x, y } => {
+                if let Some(calibration) = calibration {
+                    let (x, y) = calibration.apply(x, y);
+                    event.event = ManyMouseEvent::NormalizedAbsoluteMotion { x, y };
+                }
+                Some(event)
+            }
+            _ => Some(event),
+        }
+    }
+    /// Drains every pending event and sums them up per device.
+    pub fn frame(&mut self) -> &Frame {
+        self.frame.begin();
+        while let Some(event) = self.poll() {
+            self.frame.add_event(&event);
+        }
+        &self.frame
     }
     pub fn driver_name(&self) -> Cow<'static, str> {
         self.driver.driver_name()
@@ -46,9 +130,120 @@ impl Context {
     pub fn device_name(&self, id: usize) -> Option<&CStr> {
         self.driver.device_name(id)
     }
+    pub fn device_kind(&self, id: usize) -> Option<DeviceKind> {
+        self.driver.device_kind(id)
+    }
+    /// Overrides what the driver took the device for, like a light gun it didn't recognize.
+    ///
+    /// Returns false if the driver in use can't do this or there is no such device.
+    pub fn set_device_kind(&mut self, id: usize, kind: DeviceKind) -> bool {
+        self.driver.set_device_kind(id, kind)
+    }
+    pub fn device_identity(&self, id: usize) -> Option<DeviceIdentity> {
+        self.driver.device_identity(id)
+    }
+    /// What the device was doing when it got opened, like buttons that were already held.
+    pub fn device_state(&self, id: usize) -> Option<DeviceState> {
+        self.driver.device_state(id)
+    }
+    /// Queues events for every button the device holds down and every absolute axis it has,
+    /// as far as they weren't reported yet. Right after opening, that is everything.
+    ///
+    /// Returns false if the driver in use can't do this or there is no such device.
+    pub fn queue_state(&mut self, id: usize) -> bool {
+        self.driver.queue_state(id)
+    }
+    /// Picks the clock for `Event::timestamp`, `Clock::Monotonic` unless set otherwise.
+    ///
+    /// Returns false if the driver in use has no timestamps.
+    pub fn set_clock(&mut self, clock: Clock) -> bool {
+        self.driver.set_clock(clock)
+    }
+    /// Takes away access to the device's file descriptor, like a compositor does on a VT switch.
+    ///
+    /// The device reports `Suspend` and stays quiet until `resume_device`, it is never reopened
+    /// on its own. Returns false if the driver in use can't do this or there is no such device.
+    pub fn revoke_device(&mut self, id: usize) -> bool {
+        self.driver.revoke_device(id)
+    }
+    /// Reopens a device that got revoked by `revoke_device`.
+    ///
+    /// Returns false if it isn't suspended or can't be opened again.
+    pub fn resume_device(&mut self, id: usize) -> bool {
+        self.driver.resume_device(id)
+    }
     pub fn get_all_mouse_ids(&self) -> impl Iterator<Item = usize> + '_ {
         self.driver.get_all_ids()
     }
+    /// Reports touchpads and tablets as relative motion instead of absolute positions.
+    ///
+    /// Lifting the finger resets the conversion and touches are no longer reported as a button.
+    /// Returns false if the driver in use doesn't support this.
+    pub fn set_absolute_to_relative(&mut self, enabled: bool) -> bool {
+        self.driver.set_absolute_to_relative(enabled)
+    }
+    /// Opens keyboards as devices of their own, each reporting its keys with `Key`.
+    ///
+    /// Turning it back off releases their keys and reports them with `Disconnect`, turning it
+    /// on again brings them back with their old ids. Returns false if the driver in use can't
+    /// do this.
+    pub fn set_keyboards_enabled(&mut self, enabled: bool) -> bool {
+        self.driver.set_keyboards_enabled(enabled)
+    }
+    /// Hands out what the kernel sent as `Raw` events, next to or instead of the usual ones.
+    ///
+    /// Returns false if the driver in use doesn't get to see that, like the ManyMouse one.
+    pub fn set_raw_mode(&mut self, mode: RawMode) -> bool {
+        self.driver.set_raw_mode(mode)
+    }
+    /// Reports absolute devices with `NormalizedAbsoluteMotion` instead of `AbsoluteMotion`.
+    ///
+    /// There is one for every report of the device, with X and Y moved together. Nothing is
+    /// reported for a device until both its X and Y position are known.
+    pub fn set_normalized_absolute(&mut self, enabled: bool) {
+        self.normalized_absolute = enabled;
+    }
+    /// Maps the touches of a device with `[a, b, c, d, e, f]` before they are reported,
+    /// so `x` becomes `a * x + b * y + c` and `y` becomes `d * x + e * y + f`.
+    ///
+    /// This can rotate a touchscreen or scale it to the pixels of its output. `None` goes back
+    /// to the 0.0 to 1.0 range of the device itself.
+    pub fn set_touch_matrix(&mut self, device_id: usize, matrix: Option<[f32; 6]>) {
+        match matrix {
+            Some(matrix) => self.touch_matrices.insert(device_id, matrix),
+            None => self.touch_matrices.remove(&device_id),
+        };
+    }
+    /// The last position of an absolute device from 0.0 to 1.0, before any calibration.
+    ///
+    /// This is what to record as the reported position of a reference point.
+    pub fn absolute_position(&self, device_id: usize) -> Option<(f32, f32)> {
+        self.absolute.position(device_id)
+    }
+    /// Reports a device with calibrated `NormalizedAbsoluteMotion` instead of `AbsoluteMotion`,
+    /// even without `set_normalized_absolute`. `None` removes the calibration.
+    pub fn set_calibration(&mut self, device_id: usize, calibration: Option<Calibration>) {
+        match calibration {
+            Some(calibration) => self.calibrations.insert(device_id, calibration),
+            None => self.calibrations.remove(&device_id),
+        };
+    }
+    pub fn calibration(&self, device_id: usize) -> Option<Calibration> {
+        self.calibrations.get(&device_id).copied()
+    }
+    /// Calibrates every connected device that has a calibration in `store`.
+    pub fn apply_calibrations(&mut self, store: &CalibrationStore) {
+        let ids: Vec<usize> = self.driver.get_all_ids().collect();
+        for id in ids {
+            let identity = match self.driver.device_identity(id) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(calibration) = store.get(&identity) {
+                self.calibrations.insert(id, *calibration);
+            }
+        }
+    }
     pub fn get_all_mouse_names(&self) -> impl Iterator<Item = &CStr> + '_ {
         self.driver.get_all_mouse_names()
     }
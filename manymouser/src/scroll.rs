@@ -0,0 +1,216 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use crate::drivers::{Event, ManyMouseEvent, ScrollAxis, SCROLL_DETENT};
+
+/* Anything left below this is handed out at once instead of decaying forever. */
+const SETTLED: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScrollAmount {
+    pub device_id: usize,
+    /// Positive is up, like the wheel itself.
+    pub lines_vertical: f32,
+    /// Positive is right.
+    pub lines_horizontal: f32,
+    pub pixels_vertical: f32,
+    pub pixels_horizontal: f32,
+}
+
+/// Turns wheel events into lines and pixels to scroll by.
+pub struct ScrollProcessor {
+    lines_per_detent: f32,
+    pixels_per_line: f32,
+    inertia: Option<Duration>,
+    natural: HashSet<usize>,
+    pending: HashMap<usize, (f32, f32)>,
+}
+
+impl Default for ScrollProcessor {
+    fn default() -> Self {
+        Self {
+            lines_per_detent: 3.0,
+            pixels_per_line: 16.0,
+            inertia: None,
+            natural: HashSet::new(),
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl ScrollProcessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set_lines_per_detent(&mut self, lines: f32) {
+        self.lines_per_detent = lines;
+    }
+    pub fn set_pixels_per_line(&mut self, pixels: f32) {
+        self.pixels_per_line = pixels;
+    }
+    /// Spreads every scroll out over time instead of applying it at once.
+    ///
+    /// `time` is how long it takes for about two thirds of a scroll to be handed out by `update`.
+    /// `None` turns it back off, anything still pending is then lost.
+    pub fn set_inertia(&mut self, time: Option<Duration>) {
+        self.inertia = time;
+        if time.is_none() {
+            self.pending.clear();
+        }
+    }
+    /// Inverts the scroll direction of the given device.
+    pub fn set_natural_scroll(&mut self, device_id: usize, natural: bool) {
+        if natural {
+            self.natural.insert(device_id);
+        } else {
+            self.natural.remove(&device_id);
+        }
+    }
+
+    /// Returns how far to scroll right away. With inertia turned on this is always `None`
+    /// and the amount comes out of `update` instead.
+    pub fn handle_event(&mut self, event: &Event) -> Option<ScrollAmount> {
+        let (axis, value) = match event.event {
+            ManyMouseEvent::Scroll { axis, value } => (axis, value),
+            ManyMouseEvent::Disconnect => {
+                self.pending.remove(&event.device_id);
+                return None;
+            }
+            _ => return None,
+        };
+        let mut lines = value as f32 / SCROLL_DETENT as f32 * self.lines_per_detent;
+        if self.natural.contains(&event.device_id) {
+            lines = -lines;
+        }
+        let (vertical, horizontal) = match axis {
+            ScrollAxis::Vertical => (lines, 0.0),
+            ScrollAxis::Horizontal => (0.0, lines),
+        };
+        if self.inertia.is_some() {
+            let pending = self.pending.entry(event.device_id).or_default();
+            pending.0 += vertical;
+            pending.1 += horizontal;
+            None
+        } else {
+            Some(self.amount(event.device_id, vertical, horizontal))
+        }
+    }
+
+    /// Hands out the part of the pending scrolls that should have happened within `elapsed`.
+    pub fn update(&mut self, elapsed: Duration) -> Vec<ScrollAmount> {
+        let inertia = match self.inertia {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+        let share = if inertia.as_secs_f32() > 0.0 {
+            1.0 - (-elapsed.as_secs_f32() / inertia.as_secs_f32()).exp()
+        } else {
+            1.0
+        };
+        let mut amounts = Vec::new();
+        for (device_id, pending) in self.pending.iter_mut() {
+            let mut vertical = pending.0 * share;
+            let mut horizontal = pending.1 * share;
+            if (pending.0 - vertical).abs() < SETTLED && (pending.1 - horizontal).abs() < SETTLED {
+                vertical = pending.0;
+                horizontal = pending.1;
+            }
+            pending.0 -= vertical;
+            pending.1 -= horizontal;
+            if vertical != 0.0 || horizontal != 0.0 {
+                amounts.push((*device_id, vertical, horizontal));
+            }
+        }
+        self.pending.retain(|_, v| v.0 != 0.0 || v.1 != 0.0);
+        amounts
+            .into_iter()
+            .map(|(id, vertical, horizontal)| self.amount(id, vertical, horizontal))
+            .collect()
+    }
+
+    fn amount(&self, device_id: usize, vertical: f32, horizontal: f32) -> ScrollAmount {
+        ScrollAmount {
+            device_id,
+            lines_vertical: vertical,
+            lines_horizontal: horizontal,
+            pixels_vertical: vertical * self.pixels_per_line,
+            pixels_horizontal: horizontal * self.pixels_per_line,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scroll(device_id: usize, axis: ScrollAxis, value: i32) -> Event {
+        Event {
+            device_id,
+            event: ManyMouseEvent::Scroll { axis, value },
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn lines_and_pixels() {
+        let mut scroll_processor = ScrollProcessor::new();
+        let amount = scroll_processor
+            .handle_event(&scroll(1, ScrollAxis::Vertical, SCROLL_DETENT))
+            .unwrap();
+        assert_eq!(amount.device_id, 1);
+        assert_eq!(amount.lines_vertical, 3.0);
+        assert_eq!(amount.pixels_vertical, 48.0);
+        assert_eq!(amount.lines_horizontal, 0.0);
+
+        /* a high resolution wheel, half a detent to the left. */
+        scroll_processor.set_lines_per_detent(1.0);
+        scroll_processor.set_pixels_per_line(10.0);
+        let amount = scroll_processor
+            .handle_event(&scroll(1, ScrollAxis::Horizontal, -SCROLL_DETENT / 2))
+            .unwrap();
+        assert_eq!(amount.lines_horizontal, -0.5);
+        assert_eq!(amount.pixels_horizontal, -5.0);
+    }
+
+    #[test]
+    fn natural_scroll() {
+        let mut scroll_processor = ScrollProcessor::new();
+        scroll_processor.set_natural_scroll(2, true);
+        let natural =
+            scroll_processor.handle_event(&scroll(2, ScrollAxis::Vertical, SCROLL_DETENT));
+        let normal = scroll_processor.handle_event(&scroll(3, ScrollAxis::Vertical, SCROLL_DETENT));
+        assert_eq!(natural.unwrap().lines_vertical, -3.0);
+        assert_eq!(normal.unwrap().lines_vertical, 3.0);
+    }
+
+    #[test]
+    fn inertia_hands_out_everything() {
+        let mut scroll_processor = ScrollProcessor::new();
+        scroll_processor.set_inertia(Some(Duration::from_millis(100)));
+        assert_eq!(
+            scroll_processor.handle_event(&scroll(1, ScrollAxis::Vertical, SCROLL_DETENT)),
+            None
+        );
+        let first: f32 = scroll_processor
+            .update(Duration::from_millis(100))
+            .iter()
+            .map(|v| v.lines_vertical)
+            .sum();
+        /* one time constant in, about two thirds are out. */
+        assert!((first - 3.0 * (1.0 - (-1.0f32).exp())).abs() < 1e-4);
+        let mut total = first;
+        for _ in 0..100 {
+            total += scroll_processor
+                .update(Duration::from_millis(100))
+                .iter()
+                .map(|v| v.lines_vertical)
+                .sum::<f32>();
+        }
+        assert!((total - 3.0).abs() < 1e-4);
+        assert!(scroll_processor
+            .update(Duration::from_millis(100))
+            .is_empty());
+    }
+}
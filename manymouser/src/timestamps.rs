@@ -0,0 +1,39 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::drivers::Event;
+
+/// Turns `Event::timestamp` into an `Instant`, so it can be compared with `Instant::now()`.
+#[derive(Default)]
+pub(crate) struct EventTimes {
+    /* an instant and the timestamp that goes with it, per device. */
+    anchors: HashMap<usize, (Instant, Duration)>,
+}
+
+impl EventTimes {
+    /// When the event happened, or now if the driver doesn't say.
+    pub(crate) fn time(&mut self, event: &Event) -> Instant {
+        let now = Instant::now();
+        let timestamp = match event.timestamp {
+            Some(x) => x,
+            None => return now,
+        };
+        if let Some((instant, anchor)) = self.anchors.get(&event.device_id) {
+            let time = match timestamp.checked_sub(*anchor) {
+                Some(later) => instant.checked_add(later),
+                None => instant.checked_sub(*anchor - timestamp),
+            };
+            if let Some(time) = time {
+                /* nothing happens after it got polled, so a later time means the anchor was
+                 * taken late or the clock got changed. */
+                if time <= now {
+                    return time;
+                }
+            }
+        }
+        self.anchors.insert(event.device_id, (now, timestamp));
+        now
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    drivers::{Button, DeviceKind, Event, ManyMouseEvent, ScrollAxis, SCROLL_DETENT},
+    timestamps::EventTimes,
+    Context,
+};
+
+struct Contact {
+    start: (f32, f32),
+    last: (f32, f32),
+}
+
+#[derive(Default)]
+struct DeviceState {
+    contacts: HashMap<i32, Contact>,
+    /* set when the first finger lands, cleared once all of them are lifted. */
+    tap_start: Option<Instant>,
+    tap_fingers: usize,
+    moved: bool,
+    scroll_remainder: (f32, f32),
+}
+
+/// Turns the raw touches of touchpads into clicks and scrolling.
+///
+/// Tapping with one, two or three fingers clicks the left, right or middle button.
+/// Moving two fingers scrolls.
+pub struct TouchpadGestures {
+    tap_to_click: bool,
+    two_finger_scroll: bool,
+    tap_time: Duration,
+    tap_distance: f32,
+    scroll_distance: f32,
+    devices: HashMap<usize, DeviceState>,
+    pending: VecDeque<Event>,
+    times: EventTimes,
+}
+
+impl Default for TouchpadGestures {
+    fn default() -> Self {
+        Self {
+            tap_to_click: true,
+            two_finger_scroll: true,
+            tap_time: Duration::from_millis(180),
+            tap_distance: 0.03,
+            scroll_distance: 0.05,
+            devices: HashMap::new(),
+            pending: VecDeque::new(),
+            times: EventTimes::default(),
+        }
+    }
+}
+
+impl TouchpadGestures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set_tap_to_click(&mut self, enabled: bool) {
+        self.tap_to_click = enabled;
+    }
+    pub fn set_two_finger_scroll(&mut self, enabled: bool) {
+        self.two_finger_scroll = enabled;
+    }
+    /// Fingers that stay down longer than this don't count as a tap.
+    pub fn set_tap_time(&mut self, time: Duration) {
+        self.tap_time = time;
+    }
+    /// How far a finger may move, as a fraction of the touchpad, and still count as a tap.
+    pub fn set_tap_distance(&mut self, distance: f32) {
+        self.tap_distance = distance;
+    }
+    /// How far two fingers have to move, as a fraction of the touchpad, to scroll one detent.
+    pub fn set_scroll_distance(&mut self, distance: f32) {
+        self.scroll_distance = distance;
+    }
+
+    /// Polls `context`, handing out the generated clicks and scrolls before the next event.
+    pub fn poll(&mut self, context: &mut Context) -> Option<Event> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let event = context.poll()?;
+        let generated = self.handle_event(&event, context.device_kind(event.device_id));
+        self.pending.extend(generated);
+        Some(event)
+    }
+
+    /// Returns the clicks and scrolls that this event resulted in.
+    ///
+    /// `kind` is what `Context::device_kind` says about the device. Only touchpads make
+    /// gestures, the touches of a touchscreen already say where they are.
+    pub fn handle_event(&mut self, event: &Event, kind: Option<DeviceKind>) -> Vec<Event> {
+        let device_id = event.device_id;
+        /* a device that is gone has no kind anymore, but its state still has to go. */
+        if let ManyMouseEvent::Disconnect = event.event {
+            self.devices.remove(&device_id);
+            return Vec::new();
+        }
+        if kind != Some(DeviceKind::Touchpad) {
+            return Vec::new();
+        }
+        let timestamp = event.timestamp;
+        /* taps are timed by when the fingers touched, not by when that got polled. */
+        let time = self.times.time(event);
+        let mut generated = Vec::new();
+        match event.event {
+            ManyMouseEvent::TouchDown { contact, x, y } => {
+                let state = self.devices.entry(device_id).or_default();
+                if state.contacts.is_empty() {
+                    state.tap_start = Some(time);
+                    state.tap_fingers = 0;
+                    state.moved = false;
+                }
+                state.contacts.insert(
+                    contact,
+                    Contact {
+                        start: (x, y),
+                        last: (x, y),
+                    },
+                );
+                state.tap_fingers = state.tap_fingers.max(state.contacts.len());
+            }
+            ManyMouseEvent::TouchMove { contact, x, y } => {
+                let state = self.devices.entry(device_id).or_default();
+                let scrolling = state.contacts.len() == 2;
+                let (delta, from_start) = match state.contacts.get_mut(&contact) {
+                    Some(v) => {
+                        let delta = (x - v.last.0, y - v.last.1);
+                        v.last = (x, y);
+                        (delta, (x - v.start.0).hypot(y - v.start.1))
+                    }
+                    None => return Vec::new(),
+                };
+                if from_start > self.tap_distance {
+                    state.moved = true;
+                }
+                if scrolling && self.two_finger_scroll && self.scroll_distance > 0.0 {
+                    /* both fingers move, so each one only counts for half. */
+                    let units = SCROLL_DETENT as f32 / self.scroll_distance / 2.0;
+                    /* fingers going down scroll down, like dragging a wheel. */
+                    state.scroll_remainder.0 += -delta.1 * units;
+                    state.scroll_remainder.1 += delta.0 * units;
+                    let vertical = state.scroll_remainder.0.trunc();
+                    let horizontal = state.scroll_remainder.1.trunc();
+                    state.scroll_remainder.0 -= vertical;
+                    state.scroll_remainder.1 -= horizontal;
+                    if vertical != 0.0 {
+                        generated.push(ManyMouseEvent::Scroll {
+                            axis: ScrollAxis::Vertical,
+                            value: vertical as i32,
+                        });
+                    }
+                    if horizontal != 0.0 {
+                        generated.push(ManyMouseEvent::Scroll {
+                            axis: ScrollAxis::Horizontal,
+                            value: horizontal as i32,
+                        });
+                    }
+                }
+            }
+            ManyMouseEvent::TouchUp { contact } => {
+                let tap_time = self.tap_time;
+                let state = self.devices.entry(device_id).or_default();
+                state.contacts.remove(&contact);
+                if !state.contacts.is_empty() {
+                    return Vec::new();
+                }
+                state.scroll_remainder = (0.0, 0.0);
+                let is_tap = !state.moved
+                    && state
+                        .tap_start
+                        .take()
+                        .map(|v| time.saturating_duration_since(v) <= tap_time)
+                        .unwrap_or(false);
+                let side = match state.tap_fingers {
+                    1 => Button::Left,
+                    2 => Button::Right,
+                    3 => Button::Middle,
+                    _ => return Vec::new(),
+                };
+                if is_tap && self.tap_to_click {
+                    generated.push(ManyMouseEvent::Button {
+                        side,
+                        is_pressed: true,
+                    });
+                    generated.push(ManyMouseEvent::Button {
+                        side,
+                        is_pressed: false,
+                    });
+                }
+            }
+            _ => {}
+        }
+        generated
+            .into_iter()
+            .map(|event| Event {
+                device_id,
+                event,
+                timestamp,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOUCHPAD: Option<DeviceKind> = Some(DeviceKind::Touchpad);
+
+    fn at(millis: u64, event: ManyMouseEvent) -> Event {
+        Event {
+            device_id: 0,
+            event,
+            timestamp: Some(Duration::from_millis(millis)),
+        }
+    }
+
+    fn down(millis: u64, contact: i32, x: f32, y: f32) -> Event {
+        at(millis, ManyMouseEvent::TouchDown { contact, x, y })
+    }
+
+    fn up(millis: u64, contact: i32) -> Event {
+        at(millis, ManyMouseEvent::TouchUp { contact })
+    }
+
+    /* timestamps are lined up with now by the latest one seen, so start from a late one. */
+    fn gestures() -> TouchpadGestures {
+        let mut gestures = TouchpadGestures::new();
+        let anchor = at(
+            10_000,
+            ManyMouseEvent::TouchMove {
+                contact: -1,
+                x: 0.0,
+                y: 0.0,
+            },
+        );
+        assert!(gestures.handle_event(&anchor, TOUCHPAD).is_empty());
+        gestures
+    }
+
+    fn clicks(generated: &[Event]) -> Vec<(Button, bool)> {
+        generated
+            .iter()
+            .filter_map(|v| match v.event {
+                ManyMouseEvent::Button { side, is_pressed } => Some((side, is_pressed)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn taps() {
+        for (fingers, side) in [(1, Button::Left), (2, Button::Right), (3, Button::Middle)] {
+            let mut gestures = gestures();
+            let mut generated = Vec::new();
+            for contact in 0..fingers {
+                let x = 0.2 + 0.2 * contact as f32;
+                generated.extend(gestures.handle_event(&down(1000, contact, x, 0.5), TOUCHPAD));
+            }
+            for contact in 0..fingers {
+                generated.extend(gestures.handle_event(&up(1100, contact), TOUCHPAD));
+            }
+            assert_eq!(clicks(&generated), [(side, true), (side, false)]);
+        }
+    }
+
+    #[test]
+    fn long_press_is_no_tap() {
+        let mut gestures = gestures();
+        assert!(gestures
+            .handle_event(&down(1000, 0, 0.5, 0.5), TOUCHPAD)
+            .is_empty());
+        assert!(gestures.handle_event(&up(2000, 0), TOUCHPAD).is_empty());
+    }
+
+    #[test]
+    fn two_finger_scroll() {
+        let mut gestures = gestures();
+        let mut generated = Vec::new();
+        generated.extend(gestures.handle_event(&down(1000, 0, 0.4, 0.2), TOUCHPAD));
+        generated.extend(gestures.handle_event(&down(1000, 1, 0.6, 0.2), TOUCHPAD));
+        /* both fingers go down by twice the scroll distance. */
+        for contact in 0..2 {
+            let x = 0.4 + 0.2 * contact as f32;
+            let moved = ManyMouseEvent::TouchMove { contact, x, y: 0.3 };
+            generated.extend(gestures.handle_event(&at(1050, moved), TOUCHPAD));
+        }
+        generated.extend(gestures.handle_event(&up(1100, 0), TOUCHPAD));
+        generated.extend(gestures.handle_event(&up(1100, 1), TOUCHPAD));
+        let mut vertical = 0;
+        for event in &generated {
+            match event.event {
+                ManyMouseEvent::Scroll {
+                    axis: ScrollAxis::Vertical,
+                    value,
+                } => vertical += value,
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        /* fingers going down scroll down, like dragging a wheel. */
+        assert_eq!(vertical, -2 * SCROLL_DETENT);
+    }
+
+    #[test]
+    fn touchscreens_are_left_alone() {
+        let mut gestures = gestures();
+        let touchscreen = Some(DeviceKind::Touchscreen);
+        assert!(gestures
+            .handle_event(&down(1000, 0, 0.5, 0.5), touchscreen)
+            .is_empty());
+        assert!(gestures.handle_event(&up(1050, 0), touchscreen).is_empty());
+    }
+}